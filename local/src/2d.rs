@@ -1,29 +1,25 @@
-use highway::HighwayHasher;
-use mid_brownie_testing::{find_point, FractalNoise};
+use mid_brownie_testing::{FractalNoise, PointOrder};
 use plotters::backend::BitMapBackend;
 use plotters::chart::{ChartBuilder, ChartContext};
-use plotters::coord::types::{RangedCoordf64, RangedCoordu64};
+use plotters::coord::types::{RangedCoordf64, RangedCoordu32};
 use plotters::coord::Shift;
 use plotters::drawing::{DrawingArea, IntoDrawingArea};
 use plotters::prelude::{Cartesian2d, FontFamily, FontStyle};
 use plotters::series::LineSeries;
 use plotters::style::{Color, FontDesc, BLACK, WHITE};
-use rand::seq::IteratorRandom;
-use rand::thread_rng;
-use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::error::Error;
-use std::hash::BuildHasherDefault;
-use std::{env, iter};
 
 fn show_line(
     area: &DrawingArea<BitMapBackend, Shift>,
     i: usize,
-    chart: &mut ChartContext<BitMapBackend, Cartesian2d<RangedCoordu64, RangedCoordf64>>,
-    values: &HashMap<[u64; 1], f64, BuildHasherDefault<HighwayHasher>>,
+    chart: &mut ChartContext<BitMapBackend, Cartesian2d<RangedCoordu32, RangedCoordf64>>,
+    noise: &FractalNoise<1>,
 ) -> Result<(), Box<dyn Error>> {
-    let values_sorted = values.iter().collect::<BTreeMap<_, _>>();
     let series = LineSeries::new(
-        values_sorted.into_iter().map(|(&[k], &v)| (k, v)),
+        noise
+            .ordered_values(PointOrder::RowMajor, None, None)
+            .map(|([k], v)| (k, v)),
         BLACK.stroke_width(5),
     );
     chart.draw_series(series)?;
@@ -38,28 +34,24 @@ fn show_line(
 
 fn main() -> Result<(), Box<dyn Error>> {
     let seed = env::args().nth(1).map_or(0, |s| s.parse().unwrap());
-    const NOISE: u64 = 10000u64;
+    const NOISE: f64 = 10000.0;
     const ITERATIONS: usize = 16;
     let decay = 0.5f64;
 
-    let max = NOISE as f64 / (1f64 - decay);
-
-    let mut noise = FractalNoise::<1>::new(max / 2.0, NOISE, decay, seed);
+    let mut noise = FractalNoise::<1>::new(NOISE, decay, seed);
+    let max = noise.upper_bound(0);
 
     let area = BitMapBackend::gif("2d.gif", (1080, 1080), 1_000)?.into_drawing_area();
-    let values = loop {
-        if !noise.step_midpoints()? {
-            break noise.into_values();
-        };
+    while noise.step_midpoints()? {
         area.fill(&WHITE)?;
 
-        let mut chart = ChartBuilder::on(&area).build_cartesian_2d(0..u64::MAX, 0f64..max)?;
-        show_line(&area, noise.iterations(), &mut chart, noise.values())?;
+        let mut chart = ChartBuilder::on(&area).build_cartesian_2d(0..u32::MAX, 0f64..max)?;
+        show_line(&area, noise.iterations(), &mut chart, &noise)?;
 
         if noise.iterations() > ITERATIONS {
-            break noise.into_values();
+            break;
         }
-    };
+    }
 
     Ok(())
 }
@@ -1,37 +1,35 @@
-use highway::HighwayHasher;
-use mid_brownie_testing::{find_point, FractalNoise};
+use mid_brownie_testing::field::NoiseField;
+use mid_brownie_testing::FractalNoise;
 use plotters::backend::BitMapBackend;
 use plotters::chart::{ChartBuilder, ChartContext};
 use plotters::coord::cartesian::Cartesian3d;
-use plotters::coord::types::{RangedCoordf64, RangedCoordu64};
+use plotters::coord::types::{RangedCoordf64, RangedCoordu32};
 use plotters::coord::Shift;
 use plotters::drawing::{DrawingArea, IntoDrawingArea};
 use plotters::prelude::{FontFamily, FontStyle};
 use plotters::series::SurfaceSeries;
 use plotters::style::{Color, FontDesc, ShapeStyle, BLACK, BLUE, WHITE};
-use rand::seq::IteratorRandom;
-use rand::thread_rng;
 use std::collections::HashMap;
 use std::error::Error;
-use std::hash::BuildHasherDefault;
 use std::{env, iter};
 
 fn show_surface(
     area: &DrawingArea<BitMapBackend, Shift>,
-    midpoint: u64,
+    midpoint: u32,
     i: usize,
     chart: &mut ChartContext<
         BitMapBackend,
-        Cartesian3d<RangedCoordu64, RangedCoordf64, RangedCoordu64>,
+        Cartesian3d<RangedCoordu32, RangedCoordf64, RangedCoordu32>,
     >,
-    values: &HashMap<[u64; 2], f64, BuildHasherDefault<HighwayHasher>>,
+    field: &mut impl NoiseField<2>,
 ) -> Result<(), Box<dyn Error>> {
-    let series = SurfaceSeries::xoz(
-        iter::successors(Some(0), |s: &u64| s.checked_add(midpoint)),
-        iter::successors(Some(0), |s: &u64| s.checked_add(midpoint)),
-        |x, z| values.get(&[x, z]).copied().unwrap(),
-    )
-    .style(ShapeStyle::from(BLUE.mix(0.5)).stroke_width(0));
+    let axis = || iter::successors(Some(0), move |s: &u32| s.checked_add(midpoint));
+    let values = axis()
+        .flat_map(|x| axis().map(move |z| [x, z]))
+        .map(|point| (point, field.find_point(point)))
+        .collect::<HashMap<_, _>>();
+    let series = SurfaceSeries::xoz(axis(), axis(), |x, z| values[&[x, z]])
+        .style(ShapeStyle::from(BLUE.mix(0.5)).stroke_width(0));
 
     chart.draw_series(series)?;
 
@@ -45,38 +43,29 @@ fn show_surface(
 
 fn main() -> Result<(), Box<dyn Error>> {
     let seed = env::args().nth(1).map_or(0, |s| s.parse().unwrap());
-    const NOISE: u64 = 10000u64;
-    const ITERATIONS: usize = 10;
-    let noise = NOISE;
+    const NOISE: f64 = 10000.0;
+    const ITERATIONS: usize = 6;
     let decay = 0.5f64;
 
-    let max = noise as f64 / (1f64 - decay);
-
-    let mut noise = FractalNoise::<2>::new(noise, decay, seed);
+    let mut noise = FractalNoise::<2>::new(NOISE, decay, seed);
+    let heights = NoiseField::height_bounds(&noise);
 
     let area = BitMapBackend::gif("3d.gif", (1080, 1080), 1_000)?.into_drawing_area();
-    let values = loop {
-        let midpoint = 1u64.reverse_bits() >> noise.iterations();
-
-        if !noise.step_midpoints()? {
-            break noise.into_values();
-        };
+    while noise.step_midpoints()? {
+        let midpoint = 1u32.reverse_bits() >> (noise.iterations() - 1);
         area.fill(&WHITE)?;
 
-        let mut chart =
-            ChartBuilder::on(&area).build_cartesian_3d(0..u64::MAX, 0f64..max, 0..u64::MAX)?;
-        show_surface(
-            &area,
-            midpoint,
-            noise.iterations(),
-            &mut chart,
-            noise.values(),
+        let mut chart = ChartBuilder::on(&area).build_cartesian_3d(
+            0..u32::MAX,
+            *heights.start()..*heights.end(),
+            0..u32::MAX,
         )?;
+        show_surface(&area, midpoint, noise.iterations(), &mut chart, &mut noise)?;
 
         if noise.iterations() > ITERATIONS {
-            break noise.into_values();
+            break;
         }
-    };
+    }
 
     Ok(())
 }
@@ -1,54 +1,54 @@
-use cgmath::{Angle, Deg, InnerSpace, PerspectiveFov, Point3, Vector3};
-use mid_brownie_testing::{FractalNoise, Ray};
-use plotters::backend::BitMapBackend;
-use plotters::drawing::IntoDrawingArea;
-use plotters::prelude::RGBColor;
-use rayon::iter::{ParallelBridge, ParallelIterator};
+use cgmath::{Deg, Point3, Vector3};
+use mid_brownie_testing::camera::Camera;
+use mid_brownie_testing::render::{ColourMap, Light, Renderer, Shadows};
+use mid_brownie_testing::world::{WorldField, WorldTransform};
+use mid_brownie_testing::FractalNoise;
 use std::error::Error;
-use std::iter;
+use std::fs::File;
+use std::io::BufWriter;
 
 fn main() -> Result<(), Box<dyn Error>> {
     const DIM: u32 = 720;
-    let area = BitMapBackend::new("3d.png", (DIM, DIM)).into_drawing_area();
 
+    // the lattice spans this many metres along both axes
+    const EXTENT: f64 = 40_000.0;
     let mut cache3d = FractalNoise::<2>::new(1000.0, 0.5, 1);
 
-    let resolution = area.dim_in_pixel();
     let max = cache3d.upper_bound(0);
+    let mut world = WorldField::new(cache3d, WorldTransform::spanning([EXTENT, EXTENT], 1.0));
+    let camera = Camera::perspective(
+        Point3::new(EXTENT * 0.1, max * 2.0, EXTENT * 0.1),
+        Point3::new(EXTENT * 0.4, 0.0, EXTENT * 0.4),
+        Deg(60.0),
+        1.0,
+    );
 
-    // let origin = Point3::new(-(1i64 << 18) as f64, average, -(1i64 << 18) as f64);
+    let lights = vec![
+        Light::Directional {
+            direction: Vector3::new(-1.0, 0.4, 0.3),
+            colour: [1.0, 0.95, 0.85],
+            shadows: Shadows::Soft {
+                angle: 0.0093,
+                samples: 4,
+            },
+        },
+        Light::Sky {
+            colour: [0.3, 0.35, 0.45],
+            samples: 4,
+        },
+    ];
+    let renderer =
+        Renderer::new(camera, [DIM, DIM], ColourMap::terrain(0.0, max)).with_lights(lights);
+    let image = renderer.render_with_progress(&mut world, |progress| {
+        eprint!("\r{:.0}%", progress.fraction() * 100.0);
+    });
+    eprintln!();
 
-    let pixels = (0..resolution.0)
-        .flat_map(|x| {
-            iter::repeat((x, (1u64 << 31) * x as u64 / DIM as u64))
-                .zip((0..resolution.1).map(|y| (y, (1u64 << 31) * y as u64 / DIM as u64)))
-        })
-        .par_bridge()
-        .map_with(cache3d, |cache3d, ((x_pixel, x), (y_pixel, y))| {
-            // .map(|((x_pixel, x), (y_pixel, y))| {
-            let direction = Vector3::new(0f64, -1f64, 0f64).normalize();
-            let ray = Ray::new(direction, Point3::new(x as f64 + 0.5, max, y as f64 + 0.5));
-            ray.intersect(cache3d, max)
-                .map(|point| ((x_pixel as i32, y_pixel as i32), point))
-        })
-        .flatten()
-        .collect::<Vec<_>>();
-
-    for (pixel, point) in pixels {
-        let color = graymap(&point.y, max);
-        area.draw_pixel(pixel, &color)?;
-    }
-
-    area.present()?;
+    image.write_png(BufWriter::new(File::create("3d.png")?))?;
 
     Ok(())
 }
 
-fn graymap(y: &f64, max: f64) -> RGBColor {
-    let grayness = ((512.0) * ((1.0 + (*y - max) / max).powi(3))) as u8;
-    RGBColor(grayness, grayness, grayness)
-}
-
 #[cfg(test)]
 mod test {
     use cgmath::{Point3, Vector3};
@@ -69,9 +69,17 @@ mod test {
             ),
         );
 
-        let intersection = ray.intersect(&mut cache3d, max);
+        let hit = ray
+            .intersect(&mut cache3d, 0.0..f64::MAX)
+            .ok_or("the ray missed the centre")?;
 
-        println!("{intersection:?}");
+        // the noise never runs out, so the ray goes down to the lattice step around the centre
+        let centre = 1u32.reverse_bits();
+        let corners = [[0, 0], [1, 0], [0, 1], [1, 1]]
+            .map(|[x, z]| cache3d.find_point([centre + x, centre + z]));
+        assert!(hit.exact);
+        assert_eq!((hit.point.x, hit.point.z), (ray.origin().x, ray.origin().z));
+        assert!((hit.point.y - corners.iter().sum::<f64>() / 4.0).abs() < 1e-9);
 
         Ok(())
     }
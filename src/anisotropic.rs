@@ -0,0 +1,429 @@
+//! Fields that refine and roughen at different rates along each axis, such as ridgelines elongated
+//! along one axis or a time axis varying more slowly than space.
+
+use crate::field::NoiseField;
+use crate::{
+    box_base, box_offset, compute_midpoint, level_bounds, BoundKind, CellAabb, CellBounds, HashMap,
+    InlinePoints, Lattice, Subdivision, EPSILON,
+};
+use cgmath::AbsDiffEq;
+use highway::HighwayHasher;
+use std::array;
+use std::error::Error;
+use std::hash::BuildHasherDefault;
+use std::iter;
+use std::ops::RangeInclusive;
+
+/// Like [`FractalNoise`](crate::FractalNoise), but axis `a` is only halved every `strides[a]`th
+/// step and its amplitude decays by `decay[a]` each time it is, so that cells are boxes rather than
+/// cubes. Steps halving no axis are skipped, and a point created between parents differing along
+/// several axes is displaced with the amplitude of the roughest of them. With every stride 1 and
+/// every decay equal, the values match `FractalNoise::new(noise, decay, seed)`.
+///
+/// As a [`NoiseField`], the cubes that [`Ray`](crate::Ray) and other tools descend through take
+/// the bounds of the deepest box holding them.
+#[derive(Debug, Clone)]
+pub struct AnisotropicNoise<const N: usize> {
+    values: HashMap<[u32; N], f64>,
+    decay: [f64; N],
+    strides: [usize; N],
+    /// How often each axis has been halved before each level, and after the last one.
+    splits: Vec<[u32; N]>,
+    /// The level after which each axis has been halved a given number of times.
+    first_levels: [[usize; 33]; N],
+    axis_noise: [[f64; 32]; N],
+    /// The level from which no step displaces by more than [`EPSILON`].
+    quiet: usize,
+    bound_kind: BoundKind,
+    displacement_bounds: Vec<f64>,
+    seed: i64,
+}
+
+impl<const N: usize> AnisotropicNoise<N> {
+    pub fn new(
+        noise: f64,
+        decay: [f64; N],
+        strides: [usize; N],
+        seed: i64,
+    ) -> Result<Self, Box<dyn Error>> {
+        if strides.contains(&0) {
+            return Err("every axis must be halved at some rate".into());
+        }
+        let axis_noise = decay.map(|decay| {
+            iter::successors(Some(noise), |&prev| Some(prev * decay))
+                .take(32)
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap()
+        });
+
+        let mut splits = vec![[0u32; N]];
+        let mut first_levels = [[0usize; 33]; N];
+        for tick in 0.. {
+            let mut next = *splits.last().unwrap();
+            if next.iter().all(|&s| s == 32) {
+                break;
+            }
+            let mut any = false;
+            for (axis, s) in next.iter_mut().enumerate() {
+                if *s < 32 && tick % strides[axis] == 0 {
+                    *s += 1;
+                    first_levels[axis][*s as usize] = splits.len() - 1;
+                    any = true;
+                }
+            }
+            if any {
+                splits.push(next);
+            }
+        }
+
+        let mut result = Self {
+            values: HashMap::with_hasher(BuildHasherDefault::<HighwayHasher>::default()),
+            decay,
+            strides,
+            splits,
+            first_levels,
+            axis_noise,
+            quiet: 0,
+            bound_kind: BoundKind::default(),
+            displacement_bounds: Vec::new(),
+            seed,
+        };
+        result.update_bounds();
+        // for a decaying schedule this is the first quiet level, where FractalNoise terminates
+        result.quiet = (0..result.levels())
+            .rposition(|level| !result.noise(level).abs_diff_eq(&0.0, EPSILON))
+            .map_or(0, |level| level + 1);
+        let mut bounds = (0..result.levels())
+            .map(|level| result.noise(level))
+            .collect::<Vec<_>>();
+        for i in (0..bounds.len() - 1).rev() {
+            bounds[i] += bounds[i + 1];
+        }
+        result.values.insert([0u32; N], bounds[0] / 2.0);
+        Ok(result)
+    }
+
+    pub fn with_bound_kind(mut self, kind: BoundKind) -> Self {
+        self.bound_kind = kind;
+        self.update_bounds();
+        self
+    }
+
+    fn update_bounds(&mut self) {
+        let displacements = (0..self.levels())
+            .map(|level| self.noise(level) * 0.5)
+            .collect::<Vec<_>>();
+        self.displacement_bounds = level_bounds::<N>(self.bound_kind, &displacements);
+    }
+
+    pub fn bound_kind(&self) -> BoundKind {
+        self.bound_kind
+    }
+
+    pub fn decay(&self) -> [f64; N] {
+        self.decay
+    }
+
+    pub fn strides(&self) -> [usize; N] {
+        self.strides
+    }
+
+    pub fn seed(&self) -> i64 {
+        self.seed
+    }
+
+    pub fn values(&self) -> &HashMap<[u32; N], f64> {
+        &self.values
+    }
+
+    /// How many levels it takes to halve every axis down to single lattice steps.
+    pub fn levels(&self) -> usize {
+        self.splits.len() - 1
+    }
+
+    /// The side lengths of the cells at `level`.
+    pub fn cell_size(&self, level: usize) -> [u64; N] {
+        let splits = self.splits[level.min(self.levels())];
+        splits.map(|s| 1 << (32 - s))
+    }
+
+    /// The largest amplitude of the points created at `level`.
+    pub fn noise(&self, level: usize) -> f64 {
+        (0..N)
+            .filter(|&axis| self.splits[level][axis] < self.splits[level + 1][axis])
+            .map(|axis| self.axis_noise[axis][self.splits[level][axis] as usize])
+            .fold(0.0, f64::max)
+    }
+
+    /// How far any point created at `level` or later may stray from the corners of its cell.
+    pub fn displacement_bound(&self, level: usize) -> f64 {
+        self.displacement_bounds.get(level).copied().unwrap_or(0.0)
+    }
+
+    /// Every value lies within this range.
+    pub fn height_bounds(&self) -> RangeInclusive<f64> {
+        let root = self.values[&[0u32; N]];
+        let spread = (0..self.levels())
+            .map(|level| self.noise(level) * 0.5)
+            .sum::<f64>();
+        (root - spread)..=(root + spread)
+    }
+
+    /// The value at `n`, computing it and its ancestors if needed.
+    pub fn find_point(&mut self, n: [u32; N]) -> f64 {
+        with_corners!(f64, self.walk_from(n, 0))
+    }
+
+    /// The bounds of the box at `level` containing `point`, computing its corners if needed.
+    pub fn cell_bounds(&mut self, point: [u32; N], level: usize) -> AnisotropicCellBounds<N> {
+        let size = self.cell_size(level);
+        let base = box_base(point, size);
+        let (min, max) = (0..1u32 << N)
+            .map(|combo| self.find_point(box_offset(base, size, combo)))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+        self.bounds_around(base, level, min, max)
+    }
+
+    /// Like [`FractalNoise::cached_bounds_for`](crate::FractalNoise::cached_bounds_for), refining
+    /// boxes rather than cubes.
+    pub fn cached_bounds_for(
+        &mut self,
+        point: [u32; N],
+        height: f64,
+        level: usize,
+    ) -> AnisotropicCellBounds<N> {
+        let found = with_corners!(
+            f64,
+            self.walk_bounds(
+                point,
+                height,
+                level,
+                Subdivision::Cube,
+                |noise, corners, level, _| {
+                    let (min, max) = corners
+                        .iter()
+                        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &(_, v)| {
+                            (lo.min(v), hi.max(v))
+                        });
+                    let bounds = noise.bounds_around(corners[0].0, level, min, max);
+                    CellBounds {
+                        terminated: bounds.terminated,
+                        heights: bounds.heights,
+                        base: bounds.base,
+                        level,
+                        simplex: None,
+                    }
+                }
+            )
+        );
+        AnisotropicCellBounds {
+            terminated: found.terminated,
+            heights: found.heights,
+            base: found.base,
+            size: self.cell_size(found.level),
+            level: found.level,
+        }
+    }
+
+    /// The box at `level` with its base at `base`, holding heights from `min` to `max` on its
+    /// corners.
+    fn bounds_around(
+        &self,
+        base: [u32; N],
+        level: usize,
+        min: f64,
+        max: f64,
+    ) -> AnisotropicCellBounds<N> {
+        let bound = self.displacement_bound(level);
+        AnisotropicCellBounds {
+            terminated: level >= self.quiet,
+            heights: (min - bound)..=(max + bound),
+            base,
+            size: self.cell_size(level),
+            level,
+        }
+    }
+
+    /// The deepest level whose boxes hold whole cubes of the cells at cube `level`, which is the
+    /// one before any axis is halved past the side of those cubes.
+    fn box_level(&self, level: usize) -> usize {
+        match level {
+            0..32 => (0..N)
+                .map(|axis| self.first_levels[axis][level + 1])
+                .min()
+                .unwrap_or(0),
+            _ => self.levels(),
+        }
+    }
+}
+
+impl<const N: usize> Lattice<N> for AnisotropicNoise<N> {
+    type Value = f64;
+
+    fn cache(&self) -> &HashMap<[u32; N], f64> {
+        &self.values
+    }
+
+    fn cache_mut(&mut self) -> &mut HashMap<[u32; N], f64> {
+        &mut self.values
+    }
+
+    fn walk_subdivision(&self) -> Subdivision {
+        Subdivision::Cube
+    }
+
+    fn walk_size(&self, level: usize) -> [u64; N] {
+        self.cell_size(level)
+    }
+
+    fn derive_at(
+        &self,
+        target: [u32; N],
+        f: [u32; N],
+        s: [u32; N],
+        f_val: f64,
+        s_val: f64,
+        level: usize,
+    ) -> f64 {
+        // displaced with the amplitude of the roughest axis it is off the corners of its box along
+        let size = self.cell_size(level);
+        let noise = (0..N)
+            .filter(|&axis| !(target[axis] as u64).is_multiple_of(size[axis]))
+            .map(|axis| self.axis_noise[axis][self.splits[level][axis] as usize])
+            .fold(0.0, f64::max);
+        compute_midpoint(f, s, f_val, s_val, noise, self.seed)
+    }
+
+    fn walk(&mut self, n: [u32; N]) -> f64 {
+        self.find_point(n)
+    }
+}
+
+impl<const N: usize> NoiseField<N> for AnisotropicNoise<N> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        AnisotropicNoise::find_point(self, point)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let bounds = AnisotropicNoise::cell_bounds(self, point, self.box_level(level));
+        CellBounds::new(point, level, bounds.heights(), bounds.terminated())
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        AnisotropicNoise::height_bounds(self)
+    }
+}
+
+/// Like [`CellBounds`], for a box of an [`AnisotropicNoise`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnisotropicCellBounds<const N: usize> {
+    terminated: bool,
+    heights: RangeInclusive<f64>,
+    base: [u32; N],
+    size: [u64; N],
+    level: usize,
+}
+
+impl<const N: usize> AnisotropicCellBounds<N> {
+    pub fn terminated(&self) -> bool {
+        self.terminated
+    }
+
+    pub fn heights(&self) -> RangeInclusive<f64> {
+        self.heights.clone()
+    }
+
+    pub fn base(&self) -> [u32; N] {
+        self.base
+    }
+
+    pub fn size(&self) -> [u64; N] {
+        self.size
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn contains(&self, height: f64) -> bool {
+        self.heights.contains(&height)
+    }
+
+    pub fn aabb(&self) -> CellAabb<N> {
+        CellAabb {
+            lower: self.base.map(|n| n as f64),
+            upper: array::from_fn(|axis| self.base[axis] as f64 + self.size[axis] as f64),
+            heights: self.heights(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::anisotropic::AnisotropicNoise;
+    use crate::field::NoiseField;
+    use crate::test_support::{assert_bounds_hold, assert_rays_hit, scattered};
+    use crate::{BoundKind, FractalNoise};
+
+    #[test]
+    fn isotropic_matches_fractal_noise() {
+        let mut anisotropic = AnisotropicNoise::<2>::new(10.0, [0.6, 0.6], [1, 1], 4).unwrap();
+        let mut isotropic = FractalNoise::<2>::new(10.0, 0.6, 4);
+        assert_eq!(anisotropic.levels(), 32);
+        for point in scattered::<2>(4, 200) {
+            assert_eq!(anisotropic.find_point(point), isotropic.find_point(point));
+        }
+        // including where the noise dies out and rays stop descending
+        let point = [12345, 1 << 31];
+        for level in 0..=32 {
+            assert_eq!(
+                NoiseField::cell_bounds(&mut anisotropic, point, level),
+                NoiseField::cell_bounds(&mut isotropic, point, level)
+            );
+        }
+        assert!(!isotropic.cell_bounds(point, 27).terminated());
+        assert!(isotropic.cell_bounds(point, 28).terminated());
+        let height = isotropic.find_point(point);
+        let walked = anisotropic.cached_bounds_for(point, height, 0);
+        let expected = isotropic.cached_bounds_for(point, height, 0);
+        assert_eq!(
+            (walked.level(), walked.heights(), walked.terminated()),
+            (expected.level(), expected.heights(), expected.terminated())
+        );
+        assert!(AnisotropicNoise::<2>::new(1.0, [0.5, 0.5], [1, 0], 0).is_err());
+    }
+
+    #[test]
+    fn boxes_bound_their_points() {
+        for kind in [BoundKind::Geometric, BoundKind::Dimensional] {
+            let mut noise = AnisotropicNoise::<2>::new(10.0, [0.7, 0.3], [1, 3], 9)
+                .unwrap()
+                .with_bound_kind(kind);
+            assert_eq!(noise.levels(), 53);
+            assert_eq!(noise.cell_size(3), [1 << 29, 1 << 31]);
+            let points = scattered::<2>(9, 200);
+            for &point in &points {
+                let value = noise.find_point(point);
+                for level in 0..noise.levels() + 1 {
+                    assert!(noise.cell_bounds(point, level).contains(value));
+                }
+                let height = value + 0.01;
+                let bounds = noise.cached_bounds_for(point, height, 0);
+                assert!(bounds.contains(value));
+                assert!(bounds.terminated() || !bounds.contains(height));
+            }
+            // and the cubes of the field hold them too
+            assert_bounds_hold(&mut noise, &points, 33);
+        }
+    }
+
+    #[test]
+    fn rays_hit_anisotropic_fields() {
+        // the slow axis settles before the fast one runs out of levels, so cubes terminate
+        assert_rays_hit(AnisotropicNoise::<2>::new(10.0, [0.5, 0.2], [1, 3], 5).unwrap());
+        assert_rays_hit(AnisotropicNoise::<2>::new(10.0, [0.6, 0.6], [1, 1], 4).unwrap());
+    }
+}
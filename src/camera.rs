@@ -0,0 +1,238 @@
+//! Cameras turning pixels into [`Ray`]s.
+
+use crate::Ray;
+use cgmath::{Angle, InnerSpace, Point3, Rad, Vector3};
+
+/// The steps of the R2 sequence, the inverses of the plastic number and its square.
+pub(crate) const R2: [f64; 2] = [0.754_877_666_246_692_8, 0.569_840_290_998_053_2];
+
+/// How a [`Camera`] spreads its rays over the view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Rays fan out from the position, `fov` being the vertical field of view.
+    Perspective { fov: Rad<f64> },
+    /// Parallel rays from a view `height` units tall around the position.
+    Orthographic { height: f64 },
+}
+
+/// A view of a field from `position` towards `target`, generating rays in whatever space the
+/// field is queried in, such as metres for a [`WorldField`](crate::world::WorldField).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    position: Point3<f64>,
+    target: Point3<f64>,
+    up: Vector3<f64>,
+    projection: Projection,
+    aspect: f64,
+}
+
+impl Camera {
+    /// `aspect` is the width of the view over its height.
+    pub fn perspective(
+        position: Point3<f64>,
+        target: Point3<f64>,
+        fov: impl Into<Rad<f64>>,
+        aspect: f64,
+    ) -> Self {
+        let projection = Projection::Perspective { fov: fov.into() };
+        Self::new(position, target, projection, aspect)
+    }
+
+    pub fn orthographic(
+        position: Point3<f64>,
+        target: Point3<f64>,
+        height: f64,
+        aspect: f64,
+    ) -> Self {
+        Self::new(
+            position,
+            target,
+            Projection::Orthographic { height },
+            aspect,
+        )
+    }
+
+    pub fn new(
+        position: Point3<f64>,
+        target: Point3<f64>,
+        projection: Projection,
+        aspect: f64,
+    ) -> Self {
+        Self {
+            position,
+            target,
+            up: Vector3::unit_y(),
+            projection,
+            aspect,
+        }
+    }
+
+    /// Which way is up in the image, `y` by default. Looking straight along it, `-z` is up
+    /// instead, or `x` when the view runs along `z` too.
+    pub fn with_up(mut self, up: Vector3<f64>) -> Self {
+        self.up = up;
+        self
+    }
+
+    pub fn position(&self) -> Point3<f64> {
+        self.position
+    }
+
+    pub fn target(&self) -> Point3<f64> {
+        self.target
+    }
+
+    pub fn up(&self) -> Vector3<f64> {
+        self.up
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn aspect(&self) -> f64 {
+        self.aspect
+    }
+
+    /// The ray through `screen`, which runs from `-1` to `1` left to right and bottom to top.
+    pub fn ray(&self, screen: [f64; 2]) -> Ray {
+        let forward = (self.target - self.position).normalize();
+        let right = [self.up.normalize(), -Vector3::unit_z(), Vector3::unit_x()]
+            .into_iter()
+            .map(|up| forward.cross(up))
+            // a view along `up` has no right, and a zero `up` has no direction at all
+            .find(|right| right.magnitude2() > 1e-12)
+            .unwrap_or_else(Vector3::unit_x)
+            .normalize();
+        let up = right.cross(forward);
+        let [x, y] = screen;
+        match self.projection {
+            Projection::Perspective { fov } => {
+                let half = (fov / 2.0).tan();
+                let direction = forward + right * (x * half * self.aspect) + up * (y * half);
+                Ray::new(direction, self.position)
+            }
+            Projection::Orthographic { height } => {
+                let half = height / 2.0;
+                let origin = self.position + right * (x * half * self.aspect) + up * (y * half);
+                Ray::new(forward, origin)
+            }
+        }
+    }
+
+    /// The ray through the centre of `pixel` of an image `resolution` pixels across, counting
+    /// from the top left.
+    pub fn pixel_ray(&self, pixel: [u32; 2], resolution: [u32; 2]) -> Ray {
+        self.jittered_ray(pixel, resolution, [0.5, 0.5])
+    }
+
+    /// Like [`Camera::pixel_ray`], through the point `jitter` of the way across the pixel.
+    pub fn jittered_ray(&self, pixel: [u32; 2], resolution: [u32; 2], jitter: [f64; 2]) -> Ray {
+        let [x, y] = [0, 1].map(|a| (pixel[a] as f64 + jitter[a]) / resolution[a] as f64);
+        self.ray([x * 2.0 - 1.0, 1.0 - y * 2.0])
+    }
+
+    /// `count` rays spread evenly over `pixel` along a low-discrepancy sequence, so that every
+    /// pixel is sampled the same way on every render.
+    pub fn sample_rays(
+        &self,
+        pixel: [u32; 2],
+        resolution: [u32; 2],
+        count: usize,
+    ) -> impl Iterator<Item = Ray> + '_ {
+        (0..count).map(move |i| {
+            let jitter = R2.map(|step| (0.5 + step * i as f64).fract());
+            self.jittered_ray(pixel, resolution, jitter)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::camera::Camera;
+    use crate::world::{WorldField, WorldTransform};
+    use crate::{FractalNoise, Ray};
+    use cgmath::{AbsDiffEq, Deg, InnerSpace, Point3, Vector3};
+
+    #[test]
+    fn rays_cover_the_view() {
+        let (position, target) = (Point3::new(0.0, 10.0, 0.0), Point3::new(10.0, 0.0, 0.0));
+        let camera = Camera::perspective(position, target, Deg(90.0), 2.0);
+        let centre = camera.ray([0.0, 0.0]);
+        assert!(centre
+            .direction()
+            .abs_diff_eq(&(target - position).normalize(), 1e-12));
+        assert_eq!(
+            camera.pixel_ray([1, 1], [2, 2]).direction(),
+            camera.ray([0.5, -0.5]).direction()
+        );
+
+        // the corners lie one unit up and two aside for every unit ahead
+        let corner = camera.ray([1.0, 1.0]).direction();
+        let across = corner.dot(centre.direction());
+        assert!((across - 1.0 / 6f64.sqrt()).abs() < 1e-12);
+
+        let camera = Camera::orthographic(position, target, 4.0, 1.0);
+        let [a, b] = [[-1.0, -1.0], [1.0, 1.0]].map(|screen| camera.ray(screen));
+        assert_eq!(a.direction(), b.direction());
+        assert!(((a.origin() - b.origin()).magnitude() - 32f64.sqrt()).abs() < 1e-12);
+
+        for ray in camera.sample_rays([3, 4], [8, 8], 16) {
+            let offset = ray.origin() - camera.pixel_ray([3, 4], [8, 8]).origin();
+            assert!(offset.magnitude() <= 0.5f64.sqrt() * 0.5 + 1e-12);
+        }
+    }
+
+    #[test]
+    fn cameras_look_straight_down() {
+        let (position, target) = (Point3::new(5.0, 10.0, 5.0), Point3::new(5.0, 0.0, 5.0));
+        let ground = |ray: Ray| ray.origin() + ray.direction() * 10.0 / -ray.direction().y;
+        for camera in [
+            Camera::perspective(position, target, Deg(60.0), 1.5),
+            Camera::orthographic(position, target, 4.0, 1.5),
+            Camera::orthographic(position, target, 4.0, 1.5).with_up(Vector3::new(0.0, 0.0, 0.0)),
+        ] {
+            let centre = camera.ray([0.0, 0.0]);
+            assert!(centre.direction().abs_diff_eq(&-Vector3::unit_y(), 1e-12));
+            // right is along `x` and the top of the image along `-z`
+            let [right, top] =
+                [[1.0, 0.0], [0.0, 1.0]].map(|screen| ground(camera.ray(screen)) - ground(centre));
+            assert!(
+                right.x > 0.0 && right.y.abs() < 1e-9 && right.z.abs() < 1e-9,
+                "{right:?}"
+            );
+            assert!(
+                top.z < 0.0 && top.x.abs() < 1e-9 && top.y.abs() < 1e-9,
+                "{top:?}"
+            );
+        }
+
+        // as does an `up` pointing the same way as the view
+        let camera = Camera::perspective(position, target, Deg(60.0), 1.0);
+        assert_eq!(
+            camera
+                .with_up(Vector3::new(0.0, -2.0, 0.0))
+                .ray([1.0, 1.0])
+                .direction(),
+            camera.ray([1.0, 1.0]).direction()
+        );
+    }
+
+    #[test]
+    fn cameras_see_the_terrain() {
+        let transform = WorldTransform::spanning([8192.0, 8192.0], 200.0);
+        let mut world = WorldField::new(FractalNoise::<2>::new(1.0, 0.5, 1), transform);
+        let camera = Camera::perspective(
+            Point3::new(1000.0, 600.0, 1000.0),
+            Point3::new(3000.0, 0.0, 3000.0),
+            Deg(60.0),
+            1.0,
+        )
+        .with_up(Vector3::unit_y());
+        let hits = (0..8)
+            .flat_map(|x| (0..8).map(move |y| [x, y]))
+            .filter_map(|pixel| world.intersect(&camera.pixel_ray(pixel, [8, 8]), 0.0..f64::MAX))
+            .count();
+        assert!(hits > 32, "{hits}");
+    }
+}
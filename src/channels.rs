@@ -0,0 +1,264 @@
+//! Several correlated fields, such as height, moisture and temperature, sharing one lattice.
+
+use crate::{
+    cell_size, offset, BoundKind, CellBounds, FractalNoise, HashMap, InlinePoints, Lattice,
+    Subdivision, EPSILON,
+};
+use cgmath::AbsDiffEq;
+use highway::HighwayHasher;
+use std::array;
+use std::error::Error;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::ops::{Mul, RangeInclusive};
+
+/// `C` [`FractalNoise`] fields stored together, so that all channels share one cache and one walk
+/// per query. Each channel keeps everything its field was configured with, from amplitudes and
+/// seed to pins, modulation and transforms.
+#[derive(Debug, Clone)]
+pub struct MultiChannelNoise<const N: usize, const C: usize> {
+    values: HashMap<[u32; N], [f64; C]>,
+    channels: [FractalNoise<N>; C],
+    iterations: usize,
+}
+
+impl<const N: usize, const C: usize> MultiChannelNoise<N, C> {
+    /// Channel `c` matches `FractalNoise::new(noise[c], decay[c], self.seed(c))`, where channel 0
+    /// keeps `seed` itself.
+    pub fn new(noise: [f64; C], decay: [f64; C], seed: i64) -> Self {
+        let channels = array::from_fn(|c| {
+            let seed = match c {
+                0 => seed,
+                _ => {
+                    let mut hasher = HighwayHasher::default();
+                    hasher.write_i64(seed);
+                    hasher.write_usize(c);
+                    hasher.finish() as i64
+                }
+            };
+            FractalNoise::new(noise[c], decay[c], seed)
+        });
+        Self::from_channels(channels).unwrap()
+    }
+
+    /// Stores `channels` together, each of which must not have generated anything but its root.
+    pub fn from_channels(channels: [FractalNoise<N>; C]) -> Result<Self, Box<dyn Error>> {
+        if let Some(c) = channels
+            .iter()
+            .position(|channel| channel.values.len() > 1 || channel.iterations > 0)
+        {
+            return Err(format!("channel {c} has already generated points").into());
+        }
+        let root = array::from_fn(|c| channels[c].values[&[0u32; N]]);
+        let mut values = HashMap::with_hasher(BuildHasherDefault::<HighwayHasher>::default());
+        values.insert([0u32; N], root);
+        Ok(Self {
+            values,
+            channels,
+            iterations: 0,
+        })
+    }
+
+    pub fn with_bound_kind(mut self, kind: BoundKind) -> Self {
+        self.channels = self.channels.map(|channel| channel.with_bound_kind(kind));
+        self
+    }
+
+    /// The field configuring `channel`, which holds nothing but the root.
+    pub fn channel(&self, channel: usize) -> &FractalNoise<N> {
+        &self.channels[channel]
+    }
+
+    pub fn seed(&self, channel: usize) -> i64 {
+        self.channels[channel].seed()
+    }
+
+    pub fn noise(&self, channel: usize, iterations: usize) -> f64 {
+        self.channels[channel].noise[iterations]
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn values(&self) -> &HashMap<[u32; N], [f64; C]> {
+        &self.values
+    }
+
+    /// How far any point created at `iterations` or later may stray from the corners of its cell
+    /// in `channel`.
+    pub fn displacement_bound(&self, channel: usize, iterations: usize) -> f64 {
+        self.channels[channel].displacement_bound(iterations)
+    }
+
+    /// Every value of `channel` lies within this range.
+    pub fn height_bounds(&self, channel: usize) -> RangeInclusive<f64> {
+        self.channels[channel].height_bounds()
+    }
+
+    /// Like [`FractalNoise::step_midpoints`], stopping once every channel has run out of noise.
+    pub fn step_midpoints(&mut self) -> Result<bool, Box<dyn Error>> {
+        let level = self.iterations;
+        if level >= 32
+            || self
+                .channels
+                .iter()
+                .all(|channel| channel.noise[level].abs_diff_eq(&0.0, EPSILON))
+        {
+            return Ok(false);
+        }
+        self.refine(level);
+        self.iterations += 1;
+        Ok(true)
+    }
+
+    /// All channels at `n`, computing it and its ancestors if needed.
+    pub fn find_point(&mut self, n: [u32; N]) -> [f64; C] {
+        with_corners!([f64; C], self.walk_from(n, 0))
+    }
+
+    /// The bounds of `channel` in the cell containing `point` at `level`.
+    pub fn cell_bounds(&mut self, channel: usize, point: [u32; N], level: usize) -> CellBounds<N> {
+        let size = cell_size(level);
+        let base = point.map(|n| n.checked_div(size).map_or(0, |n| n.mul(size)));
+        let (min, max) = (0..(1 << N))
+            .map(|combo| self.find_point(offset(base, size, combo))[channel])
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+        self.channels[channel].bounds_around(base, level, min, max, None)
+    }
+
+    /// Like [`FractalNoise::cached_bounds_for`], for the heights of `channel`.
+    pub fn cached_bounds_for(
+        &mut self,
+        channel: usize,
+        point: [u32; N],
+        height: f64,
+        iterations: usize,
+    ) -> CellBounds<N> {
+        with_corners!(
+            [f64; C],
+            self.walk_bounds(
+                point,
+                height,
+                iterations,
+                Subdivision::Cube,
+                |noise, corners, level, _| {
+                    let (min, max) = corners
+                        .iter()
+                        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (_, v)| {
+                            (lo.min(v[channel]), hi.max(v[channel]))
+                        });
+                    noise.channels[channel].bounds_around(corners[0].0, level, min, max, None)
+                }
+            )
+        )
+    }
+}
+
+impl<const N: usize, const C: usize> Lattice<N> for MultiChannelNoise<N, C> {
+    type Value = [f64; C];
+
+    fn cache(&self) -> &HashMap<[u32; N], [f64; C]> {
+        &self.values
+    }
+
+    fn cache_mut(&mut self) -> &mut HashMap<[u32; N], [f64; C]> {
+        &mut self.values
+    }
+
+    fn walk_subdivision(&self) -> Subdivision {
+        Subdivision::Cube
+    }
+
+    fn derive_at(
+        &self,
+        target: [u32; N],
+        f: [u32; N],
+        s: [u32; N],
+        f_val: [f64; C],
+        s_val: [f64; C],
+        level: usize,
+    ) -> [f64; C] {
+        array::from_fn(|c| {
+            let channel = &self.channels[c];
+            channel.derive(target, f, s, f_val[c], s_val[c], channel.noise[level])
+        })
+    }
+
+    fn walk(&mut self, n: [u32; N]) -> [f64; C] {
+        self.find_point(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channels::MultiChannelNoise;
+    use crate::test_support::scattered;
+    use crate::{BoundKind, DisplacementTransform, FractalNoise, Modulation};
+
+    #[test]
+    fn channels_match_single_fields() {
+        let (noise, decay) = ([1.0, 100.0, 3.0], [0.5, 0.7, 0.3]);
+        let mut channels = MultiChannelNoise::<2, 3>::new(noise, decay, 11)
+            .with_bound_kind(BoundKind::Dimensional);
+        for _ in 0..4 {
+            channels.step_midpoints().unwrap();
+        }
+        let mut singles = [0, 1, 2].map(|c| {
+            FractalNoise::<2>::new(noise[c], decay[c], channels.seed(c))
+                .with_bound_kind(BoundKind::Dimensional)
+        });
+        assert_ne!(channels.seed(1), channels.seed(2));
+
+        for point in scattered::<2>(3, 200) {
+            let values = channels.find_point(point);
+            for (c, single) in singles.iter_mut().enumerate() {
+                assert_eq!(values[c], single.find_point(point));
+                let height = single.height_bounds().start() + 0.3 * noise[c];
+                let bounds = channels.cached_bounds_for(c, point, height, 0);
+                assert_eq!(bounds, single.cached_bounds_for(point, height, 0));
+            }
+        }
+        for (&point, &values) in channels.clone().values() {
+            for (c, value) in values.into_iter().enumerate() {
+                assert!(channels.height_bounds(c).contains(&value));
+                for level in 0..6 {
+                    assert!(channels.cell_bounds(c, point, level).contains(value));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn channels_keep_their_configuration() {
+        let mut pinned = FractalNoise::<2>::new(10.0, 0.6, 4);
+        pinned.pin([1 << 30, 3 << 30], 2.0).unwrap();
+        let singles = [
+            FractalNoise::new(1.0, 0.5, 3).with_transform(DisplacementTransform::Ridged),
+            pinned,
+            FractalNoise::new(2.0, 0.5, 5).with_modulation(
+                Modulation::new(1.0, |[x, _], _| x as f64 / u32::MAX as f64).unwrap(),
+            ),
+            // a channel without any noise stays flat rather than turning into NaN
+            FractalNoise::new(0.0, 0.5, 6),
+        ];
+        let mut channels = MultiChannelNoise::from_channels(singles.clone()).unwrap();
+        let mut singles = singles;
+        for point in scattered::<2>(9, 100)
+            .into_iter()
+            .chain([[1 << 30, 3 << 30]])
+        {
+            let values = channels.find_point(point);
+            for (c, single) in singles.iter_mut().enumerate() {
+                assert_eq!(values[c].to_bits(), single.find_point(point).to_bits());
+            }
+            assert_eq!(values[3], 0.0);
+        }
+        assert!((channels.find_point([1 << 30, 3 << 30])[1] - 2.0).abs() < 1e-9);
+
+        let flat = channels.cell_bounds(3, [7, 7], 0);
+        assert!(flat.terminated() && flat.heights() == (0.0..=0.0));
+        assert!(MultiChannelNoise::from_channels([singles[0].clone()]).is_err());
+    }
+}
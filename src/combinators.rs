@@ -0,0 +1,349 @@
+//! Lazy compositions of [`NoiseField`]s, carrying sound bounds along so that pruned searches and
+//! [`Ray::intersect`](crate::Ray::intersect) keep working on them.
+
+use crate::field::NoiseField;
+use crate::{cell_size, CellBounds, EPSILON};
+use std::fmt::{self, Debug, Formatter};
+use std::ops::RangeInclusive;
+
+/// The heights of two fields added together.
+#[derive(Debug, Clone)]
+pub struct Sum<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Sum<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<const N: usize, A: NoiseField<N>, B: NoiseField<N>> NoiseField<N> for Sum<A, B> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        self.a.find_point(point) + self.b.find_point(point)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let (a, b) = (
+            self.a.cell_bounds(point, level),
+            self.b.cell_bounds(point, level),
+        );
+        let heights = combine(a.heights(), b.heights(), |a, b| a + b);
+        CellBounds::new(point, level, heights, a.terminated() && b.terminated())
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        combine(self.a.height_bounds(), self.b.height_bounds(), |a, b| a + b)
+    }
+}
+
+/// The heights of two fields multiplied together.
+#[derive(Debug, Clone)]
+pub struct Product<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Product<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<const N: usize, A: NoiseField<N>, B: NoiseField<N>> NoiseField<N> for Product<A, B> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        self.a.find_point(point) * self.b.find_point(point)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let (a, b) = (
+            self.a.cell_bounds(point, level),
+            self.b.cell_bounds(point, level),
+        );
+        let heights = combine(a.heights(), b.heights(), |a, b| a * b);
+        CellBounds::new(point, level, heights, a.terminated() && b.terminated())
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        combine(self.a.height_bounds(), self.b.height_bounds(), |a, b| a * b)
+    }
+}
+
+/// The lower of the heights of two fields.
+#[derive(Debug, Clone)]
+pub struct Min<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Min<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<const N: usize, A: NoiseField<N>, B: NoiseField<N>> NoiseField<N> for Min<A, B> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        self.a.find_point(point).min(self.b.find_point(point))
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let (a, b) = (
+            self.a.cell_bounds(point, level),
+            self.b.cell_bounds(point, level),
+        );
+        let heights = combine(a.heights(), b.heights(), f64::min);
+        CellBounds::new(point, level, heights, a.terminated() && b.terminated())
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        combine(self.a.height_bounds(), self.b.height_bounds(), f64::min)
+    }
+}
+
+/// The higher of the heights of two fields.
+#[derive(Debug, Clone)]
+pub struct Max<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Max<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<const N: usize, A: NoiseField<N>, B: NoiseField<N>> NoiseField<N> for Max<A, B> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        self.a.find_point(point).max(self.b.find_point(point))
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let (a, b) = (
+            self.a.cell_bounds(point, level),
+            self.b.cell_bounds(point, level),
+        );
+        let heights = combine(a.heights(), b.heights(), f64::max);
+        CellBounds::new(point, level, heights, a.terminated() && b.terminated())
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        combine(self.a.height_bounds(), self.b.height_bounds(), f64::max)
+    }
+}
+
+/// The heights of a field times `scale` plus `offset`.
+#[derive(Debug, Clone)]
+pub struct Scale<F> {
+    field: F,
+    scale: f64,
+    offset: f64,
+}
+
+impl<F> Scale<F> {
+    pub fn new(field: F, scale: f64, offset: f64) -> Self {
+        Self {
+            field,
+            scale,
+            offset,
+        }
+    }
+}
+
+impl<const N: usize, F: NoiseField<N>> NoiseField<N> for Scale<F> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        self.field.find_point(point) * self.scale + self.offset
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let bounds = self.field.cell_bounds(point, level);
+        let heights = map(bounds.heights(), |h| h * self.scale + self.offset);
+        CellBounds::new(point, level, heights, bounds.terminated())
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        map(self.field.height_bounds(), |h| h * self.scale + self.offset)
+    }
+}
+
+/// The heights of a field passed through a monotone `curve`, which may rise or fall but must not
+/// do both.
+#[derive(Clone)]
+pub struct Remap<F, C> {
+    field: F,
+    curve: C,
+}
+
+impl<F, C: Fn(f64) -> f64> Remap<F, C> {
+    pub fn new(field: F, curve: C) -> Self {
+        Self { field, curve }
+    }
+}
+
+impl<F: Debug, C> Debug for Remap<F, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Remap")
+            .field("field", &self.field)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<const N: usize, F: NoiseField<N>, C: Fn(f64) -> f64> NoiseField<N> for Remap<F, C> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        (self.curve)(self.field.find_point(point))
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let bounds = self.field.cell_bounds(point, level);
+        let heights = map(bounds.heights(), &self.curve);
+        CellBounds::new(point, level, heights, bounds.terminated())
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        map(self.field.height_bounds(), &self.curve)
+    }
+}
+
+/// A field sampled at coordinates pushed along axis `a` by `strength` times the height of the
+/// `a`th warp field, rounded to the nearest lattice point.
+#[derive(Debug, Clone)]
+pub struct Warp<F, W, const N: usize> {
+    field: F,
+    warps: [W; N],
+    strength: f64,
+}
+
+impl<F, W, const N: usize> Warp<F, W, N> {
+    pub fn new(field: F, warps: [W; N], strength: f64) -> Self {
+        Self {
+            field,
+            warps,
+            strength,
+        }
+    }
+}
+
+impl<const N: usize, F: NoiseField<N>, W: NoiseField<N>> NoiseField<N> for Warp<F, W, N> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        let mut warped = point;
+        for (n, warp) in warped.iter_mut().zip(&mut self.warps) {
+            let offset = (warp.find_point(point) * self.strength).round() as i64;
+            *n = (*n as i64).wrapping_add(offset) as u32;
+        }
+        self.field.find_point(warped)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let size = cell_size(level);
+        let mut starts = point.map(|n| n.checked_div(size).map_or(0, |n| n * size));
+        let size = size as u64;
+
+        // the box the cell is warped into, as a start along each axis and the largest extent
+        let mut extent = 0u64;
+        let mut terminated = true;
+        for (start, warp) in starts.iter_mut().zip(&mut self.warps) {
+            let bounds = warp.cell_bounds(point, level);
+            terminated &= bounds.terminated();
+            let offsets = map(bounds.heights(), |h| h * self.strength);
+            let (lowest, highest) = (offsets.start().floor(), offsets.end().ceil());
+            *start = (*start as i64).wrapping_add(lowest as i64) as u32;
+            let span = (highest - lowest).min(u32::MAX as f64) as u64;
+            extent = extent.max(size + span);
+        }
+        if size == 0 || extent > 1 << 32 {
+            return CellBounds::new(point, level, self.field.height_bounds(), level >= 32);
+        }
+
+        // covered by the two cells starting from the start along each axis, at a level whose
+        // cells are at least as large as the extent
+        let covering = 32 - extent.next_power_of_two().trailing_zeros() as usize;
+        let covering_size = cell_size(covering);
+        let (mut lowest, mut highest) = (f64::INFINITY, f64::NEG_INFINITY);
+        for combo in 0..1u32 << N {
+            let mut corner = starts;
+            for (axis, n) in corner.iter_mut().enumerate() {
+                *n = n.wrapping_add(covering_size.wrapping_mul(combo >> axis & 1));
+            }
+            let bounds = self.field.cell_bounds(corner, covering);
+            terminated &= bounds.terminated();
+            let heights = bounds.heights();
+            (lowest, highest) = (lowest.min(*heights.start()), highest.max(*heights.end()));
+        }
+        // a smooth warp of a smooth field is smooth, and too flat a cell can't be told from one
+        let terminated = terminated || level >= 32 || highest - lowest <= EPSILON;
+        CellBounds::new(point, level, lowest..=highest, terminated)
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        self.field.height_bounds()
+    }
+}
+
+/// The range of `f(a, b)` for `a` and `b` in the given ranges, when `f` is monotone in each
+/// argument on its own, as reached at the corners.
+fn combine(
+    a: RangeInclusive<f64>,
+    b: RangeInclusive<f64>,
+    f: impl Fn(f64, f64) -> f64,
+) -> RangeInclusive<f64> {
+    let corners = [
+        f(*a.start(), *b.start()),
+        f(*a.start(), *b.end()),
+        f(*a.end(), *b.start()),
+        f(*a.end(), *b.end()),
+    ];
+    let lowest = corners.iter().copied().fold(f64::INFINITY, f64::min);
+    let highest = corners.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    lowest..=highest
+}
+
+/// The range of a monotone `f` over `range`.
+fn map(range: RangeInclusive<f64>, f: impl Fn(f64) -> f64) -> RangeInclusive<f64> {
+    let (start, end) = (f(*range.start()), f(*range.end()));
+    start.min(end)..=start.max(end)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::combinators::{Max, Min, Product, Remap, Scale, Sum, Warp};
+    use crate::test_support::{assert_bounds_hold, assert_rays_hit, scattered};
+    use crate::FractalNoise;
+
+    fn hills() -> FractalNoise<2> {
+        FractalNoise::new(10.0, 0.6, 1)
+    }
+
+    fn ridges() -> Scale<FractalNoise<2>> {
+        Scale::new(FractalNoise::new(4.0, 0.4, 2), -1.0, 3.0)
+    }
+
+    fn warps() -> [FractalNoise<2>; 2] {
+        [3, 4].map(|seed| FractalNoise::new(1.0, 0.5, seed))
+    }
+
+    #[test]
+    fn combined_bounds_hold() {
+        let points = scattered::<2>(1, 100);
+        assert_bounds_hold(&mut Sum::new(hills(), ridges()), &points, 8);
+        assert_bounds_hold(&mut Product::new(hills(), ridges()), &points, 8);
+        assert_bounds_hold(&mut Min::new(hills(), ridges()), &points, 8);
+        assert_bounds_hold(&mut Max::new(hills(), ridges()), &points, 8);
+        assert_bounds_hold(&mut Remap::new(hills(), |h| h * h * h), &points, 8);
+        assert_bounds_hold(&mut Remap::new(ridges(), |h| (-h).exp()), &points, 8);
+        assert_bounds_hold(&mut Warp::new(hills(), warps(), 1e8), &points, 8);
+        assert_bounds_hold(&mut Warp::new(hills(), warps(), 1e5), &points, 8);
+    }
+
+    #[test]
+    fn rays_hit_combined_fields() {
+        assert_rays_hit(Sum::new(hills(), ridges()));
+        assert_rays_hit(Product::new(hills(), ridges()));
+        assert_rays_hit(Min::new(hills(), ridges()));
+        assert_rays_hit(Max::new(hills(), ridges()));
+        assert_rays_hit(ridges());
+        assert_rays_hit(Remap::new(hills(), |h| h * h * h));
+        assert_rays_hit(Warp::new(hills(), warps(), 1e5));
+    }
+}
@@ -0,0 +1,357 @@
+//! Heightfields the raytracer and bound-pruned searches can work on, whether generated or not.
+
+use crate::heightmap::Heightmap;
+use crate::{
+    cell_index, cell_size, grid_extrema, grid_index, grid_side, offset, CellBounds, FractalNoise,
+    Subdivision,
+};
+use std::error::Error;
+use std::ops::RangeInclusive;
+
+/// A height for every point of the wrapping `u32^N` lattice, with bounds on each cell of the
+/// hierarchy that [`FractalNoise`] subdivides.
+pub trait NoiseField<const N: usize> {
+    /// The height at `point`.
+    fn find_point(&mut self, point: [u32; N]) -> f64;
+
+    /// Bounds on every height in the whole cell at `level` containing `point`, terminated once
+    /// nothing narrower can be said about the cells below.
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N>;
+
+    /// Every height lies within this range.
+    fn height_bounds(&self) -> RangeInclusive<f64>;
+
+    /// The height at corner `combo` of the cell at `level` with its base at `base`, with bit `i`
+    /// of `combo` selecting the far side along axis `i`. Corners past the end of the lattice wrap
+    /// around to its start, unless the field has something else beyond its edge.
+    fn cell_corner(&mut self, base: [u32; N], level: usize, combo: u32) -> f64 {
+        self.find_point(offset(base, cell_size(level), combo))
+    }
+
+    /// The first cell containing `point` from `level` down whose bounds exclude `height`, or the
+    /// terminated cell containing it.
+    fn cached_bounds_for(&mut self, point: [u32; N], height: f64, level: usize) -> CellBounds<N> {
+        let mut bounds = self.cell_bounds(point, level);
+        while !bounds.terminated() && bounds.contains(height) {
+            bounds = self.cell_bounds(point, bounds.level() + 1);
+        }
+        bounds
+    }
+}
+
+impl<const N: usize> NoiseField<N> for FractalNoise<N> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        FractalNoise::find_point(self, point)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        self.cell_bounds_with(point, level, Subdivision::Cube)
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        FractalNoise::height_bounds(self)
+    }
+
+    fn cached_bounds_for(&mut self, point: [u32; N], height: f64, level: usize) -> CellBounds<N> {
+        self.cached_cube_bounds_for(point, height, level)
+    }
+}
+
+impl<const N: usize, F: NoiseField<N> + ?Sized> NoiseField<N> for &mut F {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        (**self).find_point(point)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        (**self).cell_bounds(point, level)
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        (**self).height_bounds()
+    }
+
+    fn cell_corner(&mut self, base: [u32; N], level: usize, combo: u32) -> f64 {
+        (**self).cell_corner(base, level, combo)
+    }
+
+    fn cached_bounds_for(&mut self, point: [u32; N], height: f64, level: usize) -> CellBounds<N> {
+        (**self).cached_bounds_for(point, height, level)
+    }
+}
+
+impl<const N: usize, F: NoiseField<N> + ?Sized> NoiseField<N> for Box<F> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        (**self).find_point(point)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        (**self).cell_bounds(point, level)
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        (**self).height_bounds()
+    }
+
+    fn cell_corner(&mut self, base: [u32; N], level: usize, combo: u32) -> f64 {
+        (**self).cell_corner(base, level, combo)
+    }
+
+    fn cached_bounds_for(&mut self, point: [u32; N], height: f64, level: usize) -> CellBounds<N> {
+        (**self).cached_bounds_for(point, height, level)
+    }
+}
+
+/// Heights given on the corners of the cells at `level` and interpolated multilinearly between
+/// them, such as an imported [`Heightmap`].
+#[derive(Debug, Clone)]
+pub struct Raster<const N: usize> {
+    grid: Vec<f64>,
+    level: usize,
+    extrema: Vec<Vec<(f64, f64)>>,
+}
+
+impl<const N: usize> Raster<N> {
+    /// Takes the `(1 << level)^N` heights in `grid` in the order of
+    /// [`PointOrder::RowMajor`](crate::PointOrder::RowMajor), wrapping around like the lattice.
+    pub fn new(grid: Vec<f64>, level: usize) -> Result<Self, Box<dyn Error>> {
+        grid_side::<N>(&grid, level)?;
+        let extrema = grid_extrema::<N>(&grid, level);
+        Ok(Self {
+            grid,
+            level,
+            extrema,
+        })
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn grid(&self) -> &[f64] {
+        &self.grid
+    }
+}
+
+impl Raster<2> {
+    /// The samples of `map` multiplied by `scale`, with rows along axis 0.
+    pub fn from_heightmap(map: &Heightmap, scale: f64) -> Result<Self, Box<dyn Error>> {
+        let level = map.level().ok_or_else(|| {
+            format!(
+                "a {}x{} map is not a power of two square",
+                map.width(),
+                map.height()
+            )
+        })?;
+        Self::new(map.heights(scale), level)
+    }
+}
+
+impl<const N: usize> NoiseField<N> for Raster<N> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        let side = 1usize << self.level;
+        let size = cell_size(self.level) as u64;
+        let shift = 32 - self.level as u32;
+        let coords = point.map(|n| n.checked_shr(shift).unwrap_or(0) as usize);
+        let fractions = point.map(|n| match size {
+            0 => 0.0,
+            _ => (n as u64 % size) as f64 / size as f64,
+        });
+        let (mut height, mut lowest, mut highest) = (0.0, f64::INFINITY, f64::NEG_INFINITY);
+        for combo in 0..1usize << N {
+            let mut corner = coords;
+            let mut weight = 1.0;
+            for (axis, c) in corner.iter_mut().enumerate() {
+                if combo >> axis & 1 == 1 {
+                    *c = (*c + 1) % side;
+                    weight *= fractions[axis];
+                } else {
+                    weight *= 1.0 - fractions[axis];
+                }
+            }
+            let value = self.grid[grid_index(corner, side)];
+            height += weight * value;
+            (lowest, highest) = (lowest.min(value), highest.max(value));
+        }
+        // keep rounding from escaping the bounds of the cell
+        height.clamp(lowest, highest)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let size = cell_size(level);
+        let base = point.map(|n| n.checked_div(size).map_or(0, |n| n * size));
+        if let Some(extrema) = self.extrema.get(level) {
+            let (lowest, highest) = extrema[cell_index(base, level)];
+            return CellBounds::new(point, level, lowest..=highest, false);
+        }
+        // multilinear interpolation never leaves the range of the corners
+        let (lowest, highest) = (0..1u32 << N)
+            .map(|combo| {
+                let mut corner = base;
+                for (axis, n) in corner.iter_mut().enumerate() {
+                    *n = n.wrapping_add(size * (combo >> axis & 1));
+                }
+                self.find_point(corner)
+            })
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), h| {
+                (lo.min(h), hi.max(h))
+            });
+        // the patch between the corners is exact from the level of the grid down
+        let terminated = level >= self.level || lowest == highest;
+        CellBounds::new(point, level, lowest..=highest, terminated)
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        let (lowest, highest) = self
+            .extrema
+            .first()
+            .map_or((self.grid[0], self.grid[0]), |extrema| extrema[0]);
+        lowest..=highest
+    }
+}
+
+/// The `M`-dimensional field seen along the `free` axes of an `N`-dimensional one, with every other
+/// axis held at its coordinate in `fixed`, such as one frame of a field whose last axis is time.
+/// Queries go straight to the wrapped field, so a `&mut` field keeps sharing its cache, and each
+/// cell borrows the bounds of the cell of the full field around it.
+#[derive(Debug, Clone)]
+pub struct Slice<F, const N: usize, const M: usize> {
+    field: F,
+    free: [usize; M],
+    fixed: [u32; N],
+}
+
+impl<F: NoiseField<N>, const N: usize, const M: usize> Slice<F, N, M> {
+    /// The coordinates `fixed` holds along the `free` axes are ignored.
+    pub fn new(field: F, free: [usize; M], fixed: [u32; N]) -> Result<Self, Box<dyn Error>> {
+        for (i, &axis) in free.iter().enumerate() {
+            if axis >= N {
+                return Err(format!("axis {axis} is out of range for {N} dimensions").into());
+            }
+            if free[..i].contains(&axis) {
+                return Err(format!("axis {axis} is free more than once").into());
+            }
+        }
+        Ok(Self { field, free, fixed })
+    }
+
+    /// The point of the full field that `point` stands for.
+    pub fn embed(&self, point: [u32; M]) -> [u32; N] {
+        let mut embedded = self.fixed;
+        for (&axis, n) in self.free.iter().zip(point) {
+            embedded[axis] = n;
+        }
+        embedded
+    }
+
+    pub fn free(&self) -> [usize; M] {
+        self.free
+    }
+
+    pub fn field(&self) -> &F {
+        &self.field
+    }
+
+    pub fn into_inner(self) -> F {
+        self.field
+    }
+}
+
+impl<F: NoiseField<N>, const N: usize, const M: usize> NoiseField<M> for Slice<F, N, M> {
+    fn find_point(&mut self, point: [u32; M]) -> f64 {
+        self.field.find_point(self.embed(point))
+    }
+
+    fn cell_bounds(&mut self, point: [u32; M], level: usize) -> CellBounds<M> {
+        let bounds = self.field.cell_bounds(self.embed(point), level);
+        CellBounds::new(point, level, bounds.heights(), bounds.terminated())
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        self.field.height_bounds()
+    }
+}
+
+impl<const N: usize> FractalNoise<N> {
+    /// A [`Slice`] of this field sharing its cache.
+    pub fn slice<const M: usize>(
+        &mut self,
+        free: [usize; M],
+        fixed: [u32; N],
+    ) -> Result<Slice<&mut Self, N, M>, Box<dyn Error>> {
+        Slice::new(self, free, fixed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::field::{NoiseField, Raster};
+    use crate::test_support::{assert_bounds_hold, scattered};
+    use crate::{FractalNoise, Ray};
+    use cgmath::{Point3, Vector3};
+
+    #[test]
+    fn raster_bounds_hold() {
+        let grid = (0..64).map(|i| ((i * 37) % 64) as f64).collect::<Vec<_>>();
+        let mut raster = Raster::<2>::new(grid.clone(), 3).unwrap();
+        assert_eq!(raster.find_point([3 << 29, 5 << 29]), grid[29]);
+        assert_eq!(raster.find_point([1 << 28, 0]), (grid[0] + grid[8]) / 2.0);
+
+        assert_bounds_hold(&mut raster, &scattered(3, 200), 12);
+        assert!(Raster::<2>::new(grid, 2).is_err());
+    }
+
+    #[test]
+    fn rays_stop_at_the_raster_level() {
+        let grid = (0..64).map(|i| (i % 8 + i / 8) as f64).collect::<Vec<_>>();
+        let mut raster = Raster::<2>::new(grid, 3).unwrap();
+        let ray = Ray::new(
+            Vector3::new(0.3, -0.01, 0.2),
+            Point3::new(1.5e9, 20.0, 1.2e9),
+        );
+        let hit = ray.intersect(&mut raster, 0.0..f64::MAX).unwrap();
+        assert_eq!(hit.level, raster.level());
+        let below = raster.find_point([hit.point.x as u32, hit.point.z as u32]);
+        assert!((below - hit.point.y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rays_work_through_the_trait() {
+        let ray = Ray::new(
+            Vector3::new(0.3, -1.0, 0.2),
+            Point3::new(1e9 + 0.5, 4.0, 2e9 + 0.5),
+        );
+        let mut direct = FractalNoise::<2>::new(1.0, 0.5, 0);
+        let mut boxed: Box<dyn NoiseField<2>> = Box::new(direct.clone());
+        let expected = ray.intersect(&mut direct, 0.0..f64::MAX);
+        assert!(expected.is_some());
+        assert_eq!(ray.intersect(&mut boxed, 0.0..f64::MAX), expected);
+    }
+
+    #[test]
+    fn slices_share_the_cache() {
+        let mut noise = FractalNoise::<3>::new(1.0, 0.5, 2);
+        let frame = 0x4000_0000;
+        let mut slice = noise.slice([0, 2], [0, frame, 0]).unwrap();
+        let point = [0x1234_5678, 0x9abc_def0];
+        let value = slice.find_point(point);
+        for level in 0..33 {
+            assert!(slice.cell_bounds(point, level).contains(value));
+        }
+        assert!(slice.height_bounds().contains(&value));
+
+        let ray = Ray::new(
+            Vector3::new(0.3, -1.0, 0.2),
+            Point3::new(1e9 + 0.5, 4.0, 2e9 + 0.5),
+        );
+        let hit = ray.intersect(&mut slice, 0.0..f64::MAX).unwrap().point;
+        let below = slice.find_point([hit.x as u32, hit.z as u32]);
+        assert!((below - hit.y).abs() < 0.1);
+
+        assert!(noise
+            .values()
+            .contains_key(&[0x1234_5678, frame, 0x9abc_def0]));
+        assert_eq!(noise.find_point([0x1234_5678, frame, 0x9abc_def0]), value);
+        assert!(noise.slice([0, 0], [0; 3]).is_err());
+        assert!(noise.slice([3], [0; 3]).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Importers for real heightmaps to refine with [`FractalNoise::from_coarse_grid`].
+
+use crate::FractalNoise;
+use std::error::Error;
+use std::io::{BufRead, Read};
+
+/// A grid of 16-bit samples, row by row from the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heightmap {
+    width: usize,
+    height: usize,
+    samples: Vec<u16>,
+}
+
+impl Heightmap {
+    pub fn new(width: usize, height: usize, samples: Vec<u16>) -> Result<Self, Box<dyn Error>> {
+        if width.checked_mul(height) != Some(samples.len()) {
+            return Err(format!("expected {width}x{height} samples, got {}", samples.len()).into());
+        }
+        Ok(Self {
+            width,
+            height,
+            samples,
+        })
+    }
+
+    /// Reads a binary (`P5`) PGM, with one byte per sample up to a maximum value of 255 and two
+    /// big-endian bytes above.
+    pub fn read_pgm(mut reader: impl BufRead) -> Result<Self, Box<dyn Error>> {
+        if pgm_token(&mut reader)? != "P5" {
+            return Err("not a binary PGM".into());
+        }
+        let width = pgm_token(&mut reader)?.parse()?;
+        let height = pgm_token(&mut reader)?.parse()?;
+        let max: u16 = pgm_token(&mut reader)?.parse()?;
+
+        let wide = max > 255;
+        let len = usize::checked_mul(width, height)
+            .and_then(|len| len.checked_mul(if wide { 2 } else { 1 }))
+            .ok_or_else(|| format!("a {width}x{height} map is too large"))?;
+        // read no more than the input holds, rather than trusting the header with the allocation
+        let mut bytes = Vec::new();
+        reader.take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() < len {
+            return Err(format!("expected {len} bytes of samples, got {}", bytes.len()).into());
+        }
+        let samples = if wide {
+            bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect()
+        } else {
+            bytes.into_iter().map(u16::from).collect()
+        };
+        Self::new(width, height, samples)
+    }
+
+    /// Reads a grayscale PNG. Depths below 8 bits are scaled up to fill a byte, but 8-bit samples
+    /// are kept as they are rather than rescaled to 16 bits.
+    pub fn read_png(reader: impl Read) -> Result<Self, Box<dyn Error>> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut bytes = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut bytes)?;
+        if info.color_type != png::ColorType::Grayscale {
+            return Err(format!("expected a grayscale PNG, got {:?}", info.color_type).into());
+        }
+
+        let (width, height) = (info.width as usize, info.height as usize);
+        let bytes = &bytes[..info.buffer_size()];
+        let samples = match info.bit_depth {
+            png::BitDepth::Sixteen => bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect(),
+            _ => bytes.iter().map(|&b| u16::from(b)).collect(),
+        };
+        Self::new(width, height, samples)
+    }
+
+    /// Reads a square raw heightmap of little-endian 16-bit samples, as exported by most terrain
+    /// tools.
+    pub fn read_r16(mut reader: impl Read) -> Result<Self, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let len = bytes.len() / 2;
+        let side = len.isqrt();
+        if bytes.len() % 2 != 0 || side * side != len {
+            return Err(format!("{} bytes are not a square of 16-bit samples", bytes.len()).into());
+        }
+        let samples = bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        Self::new(side, side, samples)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn samples(&self) -> &[u16] {
+        &self.samples
+    }
+
+    /// The level whose cell corners the samples can be, if the map is a square with a power of two
+    /// side.
+    pub fn level(&self) -> Option<usize> {
+        (self.width == self.height && self.width.is_power_of_two())
+            .then(|| self.width.trailing_zeros() as usize)
+    }
+
+    /// The samples multiplied by `scale`, with rows along axis 0.
+    pub fn heights(&self, scale: f64) -> Vec<f64> {
+        self.samples.iter().map(|&s| s as f64 * scale).collect()
+    }
+}
+
+impl FractalNoise<2> {
+    /// Refines `map` with [`FractalNoise::from_coarse_grid`], scaling its samples by `scale`. The map
+    /// covers the first half of each axis, with its mirror image in the other.
+    pub fn from_heightmap(
+        map: &Heightmap,
+        scale: f64,
+        noise: f64,
+        decay: f64,
+        seed: i64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let level = map.level().ok_or_else(|| {
+            format!(
+                "a {}x{} map is not a power of two square",
+                map.width, map.height
+            )
+        })?;
+        Self::from_coarse_grid(&map.heights(scale), level, noise, decay, seed)
+    }
+}
+
+/// The next whitespace separated header token, skipping `#` comments, and consuming the single
+/// whitespace byte after it.
+fn pgm_token(reader: &mut impl BufRead) -> Result<String, Box<dyn Error>> {
+    let mut token = String::new();
+    let mut byte = [0];
+    loop {
+        reader.read_exact(&mut byte)?;
+        match byte[0] {
+            b'#' if token.is_empty() => {
+                reader.read_until(b'\n', &mut Vec::new())?;
+            }
+            b if b.is_ascii_whitespace() => {
+                if !token.is_empty() {
+                    return Ok(token);
+                }
+            }
+            b => token.push(b as char),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::heightmap::Heightmap;
+    use png::{BitDepth, ColorType, Encoder};
+
+    #[test]
+    fn reads_pgm_and_r16() {
+        let mut pgm = b"P5\n# a comment\n2 2\n65535\n".to_vec();
+        pgm.extend([0, 1, 1, 0, 255, 255, 0, 0]);
+        let map = Heightmap::read_pgm(&pgm[..]).unwrap();
+        assert_eq!(map.samples(), [1, 256, 65535, 0]);
+        assert_eq!(map.level(), Some(1));
+
+        let map = Heightmap::read_pgm(&b"P5 1 2 255 \x07\x09"[..]).unwrap();
+        assert_eq!(
+            (map.width(), map.height(), map.samples()),
+            (1, 2, &[7, 9][..])
+        );
+        assert_eq!(map.level(), None);
+
+        let map = Heightmap::read_r16(&[1, 0, 0, 1, 2, 0, 0, 2][..]).unwrap();
+        assert_eq!(map.samples(), [1, 256, 2, 512]);
+        assert!(Heightmap::read_r16(&[1, 0, 0][..]).is_err());
+
+        let huge = format!("P5 {} 2 65535 ", usize::MAX / 2);
+        assert!(Heightmap::read_pgm(huge.as_bytes()).is_err());
+        assert!(Heightmap::read_pgm(&b"P5 4000000 4000000 255 \x07\x09"[..]).is_err());
+    }
+
+    fn png(width: u32, depth: BitDepth, colour: ColorType, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = Encoder::new(&mut bytes, width, 1);
+        encoder.set_color(colour);
+        encoder.set_depth(depth);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn reads_png() {
+        let wide = png(2, BitDepth::Sixteen, ColorType::Grayscale, &[1, 0, 0, 7]);
+        let map = Heightmap::read_png(&wide[..]).unwrap();
+        assert_eq!(
+            (map.width(), map.height(), map.samples()),
+            (2, 1, &[256, 7][..])
+        );
+
+        let narrow = png(3, BitDepth::Eight, ColorType::Grayscale, &[0, 9, 255]);
+        assert_eq!(
+            Heightmap::read_png(&narrow[..]).unwrap().samples(),
+            [0, 9, 255]
+        );
+
+        let packed = png(4, BitDepth::Two, ColorType::Grayscale, &[0b00_01_10_11]);
+        assert_eq!(
+            Heightmap::read_png(&packed[..]).unwrap().samples(),
+            [0, 85, 170, 255]
+        );
+
+        let rgb = png(1, BitDepth::Eight, ColorType::Rgb, &[1, 2, 3]);
+        assert!(Heightmap::read_png(&rgb[..]).is_err());
+    }
+}
@@ -1,23 +1,235 @@
-#![cfg_attr(nightly, feature(generic_const_exprs))]
-
 use cgmath::{AbsDiffEq, InnerSpace, Point3, Vector3, Zero};
+use std::array;
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashMap as StdHashMap;
 use std::error::Error;
+use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasherDefault, Hash, Hasher};
-use std::iter;
-use std::ops::{Add, Div, IndexMut, Mul, RangeInclusive};
+use std::ops::{Index, IndexMut, Mul, Range, RangeInclusive};
+use std::sync::{Arc, Mutex};
+use std::{iter, vec};
 
 type HashMap<T, U> = StdHashMap<T, U, BuildHasherDefault<HighwayHasher>>;
 
+use field::NoiseField;
 use highway::HighwayHasher;
 
 const EPSILON: f64 = 0.00001;
 
+/// Calls a method generic over [`ValidPointsArray`] with a buffer fitting every corner of a cell
+/// holding `$value`s, keeping corners on the stack up to `N == 8`. This stands in for a
+/// `[T; 1 << N]` buffer, which needs the incomplete `generic_const_exprs` feature and a
+/// `[(); 1 << N]:` bound on every caller, the [`NoiseField`] impls included, so stable and nightly
+/// builds share it. Every arm is type-checked and instantiated, but `N` is a constant, so the
+/// optimiser removes the arms for other dimensions as dead code.
+macro_rules! with_corners {
+    ($value:ty, $self:ident.$method:ident($($arg:expr),*)) => {
+        match N {
+            0 | 1 => $self.$method::<InlinePoints<([u32; N], $value), 2>>($($arg),*),
+            2 => $self.$method::<InlinePoints<([u32; N], $value), 4>>($($arg),*),
+            3 => $self.$method::<InlinePoints<([u32; N], $value), 8>>($($arg),*),
+            4 => $self.$method::<InlinePoints<([u32; N], $value), 16>>($($arg),*),
+            5 => $self.$method::<InlinePoints<([u32; N], $value), 32>>($($arg),*),
+            6 => $self.$method::<InlinePoints<([u32; N], $value), 64>>($($arg),*),
+            7 => $self.$method::<InlinePoints<([u32; N], $value), 128>>($($arg),*),
+            8 => $self.$method::<InlinePoints<([u32; N], $value), 256>>($($arg),*),
+            _ => $self.$method::<Vec<([u32; N], $value)>>($($arg),*),
+        }
+    };
+}
+
+pub mod anisotropic;
+pub mod camera;
+pub mod channels;
+pub mod combinators;
+pub mod field;
+pub mod heightmap;
+pub mod render;
+pub mod tiled;
+pub mod world;
+
+#[cfg(test)]
+mod test_support;
+
+/// How far [`FractalNoise::cached_bounds_for`] assumes the points below a cell can stray from its
+/// corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoundKind {
+    /// The full amplitude of every remaining level, regardless of dimension.
+    #[default]
+    Geometric,
+    /// The largest excursion reachable when every remaining displacement takes its extreme
+    /// value. For `N > 1` that is the sum of the remaining displacements, half the remaining
+    /// amplitude: a new point averages two points of its cell, so it rises at most its own
+    /// displacement above the highest point before it, and a cell with level corners, like the
+    /// root, has points whose parents both sit at that maximum on every level. For `N == 1` it
+    /// is less, as a new point can only ever average one raised parent with one unraised parent.
+    Dimensional,
+}
+
+/// Which points of a cell a query refines on its way down to a lattice point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Subdivision {
+    /// All `1 << N` corners of the hypercube containing the point.
+    #[default]
+    Cube,
+    /// Only the `N + 1` vertices of the Kuhn simplex containing the point. Every point is created
+    /// on an edge `f -> s` between two corners differing by a 0/1 vector, which is an edge of that
+    /// triangulation, so the simplex vertices of each level are bisected from those of the level
+    /// above and the values are identical to [`Subdivision::Cube`].
+    Simplex,
+}
+
+/// Reshapes how a level displaces its new points, for looks other than rolling hills.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DisplacementTransform {
+    #[default]
+    Identity,
+    /// `noise / 2 - 2|d|`, raising sharp crests wherever the displacement is small.
+    Ridged,
+    /// `2|d| - noise / 2`, the inverse of [`DisplacementTransform::Ridged`] for rounded hills.
+    Billowy,
+    /// `d|d| * 2 / noise`, keeping most displacements small with the occasional spike.
+    Squared,
+    /// Rounds the average of the parents to a multiple of `step` before displacing it.
+    Terrace { step: f64 },
+}
+
+impl DisplacementTransform {
+    /// The height a new point is displaced from, given the `average` of its parents.
+    pub fn base(self, average: f64) -> f64 {
+        match self {
+            Self::Terrace { step } if step != 0.0 => (average / step).round() * step,
+            _ => average,
+        }
+    }
+
+    /// Reshapes `displacement`, drawn from `-noise / 2..noise / 2`, within the same range.
+    pub fn displace(self, displacement: f64, noise: f64) -> f64 {
+        match self {
+            Self::Identity | Self::Terrace { .. } => displacement,
+            Self::Ridged => noise * 0.5 - displacement.abs() * 2.0,
+            Self::Billowy => displacement.abs() * 2.0 - noise * 0.5,
+            Self::Squared => match noise {
+                0.0 => 0.0,
+                _ => displacement * displacement.abs() * 2.0 / noise,
+            },
+        }
+    }
+
+    /// How far [`DisplacementTransform::base`] can move the average.
+    pub fn max_shift(self) -> f64 {
+        match self {
+            Self::Terrace { step } => step.abs() * 0.5,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PointOrder {
+    /// Along the Z-order curve, interleaving coordinate bits with axis 0 the most significant.
+    #[default]
+    Morton,
+    /// Lexicographically, with the last axis varying fastest.
+    RowMajor,
+}
+
+/// Scales the displacement of every new point by a function of its position and the average of its
+/// parents, for rough mountains next to smooth plains.
+#[derive(Clone)]
+pub struct Modulation<const N: usize> {
+    source: ModulationSource<N>,
+    max: f64,
+}
+
+enum ModulationSource<const N: usize> {
+    Function(Arc<dyn Fn([u32; N], f64) -> f64 + Send + Sync>),
+    Field(Box<Mutex<FractalNoise<N>>>, f64),
+}
+
+impl<const N: usize> Clone for ModulationSource<N> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Function(function) => Self::Function(function.clone()),
+            Self::Field(field, scale) => {
+                let field = field.lock().unwrap().clone();
+                Self::Field(Box::new(Mutex::new(field)), *scale)
+            }
+        }
+    }
+}
+
+impl<const N: usize> Modulation<N> {
+    /// Scales by `function`, clamped to `-max..=max` so the bounds stay conservative.
+    pub fn new(
+        max: f64,
+        function: impl Fn([u32; N], f64) -> f64 + Send + Sync + 'static,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            source: ModulationSource::Function(Arc::new(function)),
+            max: checked_max(max)?,
+        })
+    }
+
+    /// Scales by the height of `field` at each point times `scale`. Every clone of the modulation
+    /// refines a copy of `field` of its own, so that the clones on different threads, as in
+    /// [`Renderer::render`](render::Renderer::render), never wait on each other for it.
+    pub fn from_field(field: FractalNoise<N>, scale: f64) -> Result<Self, Box<dyn Error>> {
+        let range = field.height_bounds();
+        let max = range.start().abs().max(range.end().abs()) * scale.abs();
+        Ok(Self {
+            source: ModulationSource::Field(Box::new(Mutex::new(field)), scale),
+            max: checked_max(max)?,
+        })
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// The factor for a point at `point` whose parents average to `average`.
+    pub fn factor(&self, point: [u32; N], average: f64) -> f64 {
+        let factor = match &self.source {
+            ModulationSource::Function(function) => function(point, average),
+            ModulationSource::Field(field, scale) => {
+                field.lock().unwrap().find_point(point) * scale
+            }
+        };
+        factor.clamp(-self.max, self.max)
+    }
+}
+
+fn checked_max(max: f64) -> Result<f64, Box<dyn Error>> {
+    if !max.is_finite() {
+        return Err(format!("the modulation must be bounded, got {max}").into());
+    }
+    Ok(max.abs())
+}
+
+impl<const N: usize> Debug for Modulation<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Modulation")
+            .field("max", &self.max)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FractalNoise<const N: usize> {
     values: HashMap<[u32; N], f64>,
     noise: [f64; 32],
     bounds: [f64; 32],
+    bound_kind: BoundKind,
+    displacement_bounds: [f64; 32],
+    subdivision: Subdivision,
+    pins: Vec<([u32; N], f64)>,
+    adjustments: HashMap<[u32; N], f64>,
+    level_adjustments: [f64; 32],
+    coarse_level: usize,
+    coarse_extrema: Vec<Vec<(f64, f64)>>,
+    modulation: Option<Modulation<N>>,
+    transforms: [DisplacementTransform; 32],
     decay: f64,
     seed: i64,
     iterations: usize,
@@ -39,6 +251,16 @@ impl<const N: usize> FractalNoise<N> {
             values,
             noise,
             bounds,
+            bound_kind: BoundKind::default(),
+            displacement_bounds: bounds,
+            subdivision: Subdivision::default(),
+            pins: Vec::new(),
+            adjustments: HashMap::with_hasher(BuildHasherDefault::<HighwayHasher>::default()),
+            level_adjustments: [0.0; 32],
+            coarse_level: 0,
+            coarse_extrema: Vec::new(),
+            modulation: None,
+            transforms: [DisplacementTransform::default(); 32],
             decay,
             seed,
             iterations: 0,
@@ -48,45 +270,264 @@ impl<const N: usize> FractalNoise<N> {
         result
     }
 
-    fn next_points(
+    /// Starts from the `(1 << level)^N` heights in `grid`, in the order of [`PointOrder::RowMajor`],
+    /// and refines them with the amplitudes `noise * decay^i` of the levels `i > level`. The grid
+    /// covers the first half of each axis as the corners of the cells at `level + 1`, and is
+    /// mirrored into the other half, so that every cell along its edges lies between real samples
+    /// rather than wrapping around to the opposite edge.
+    pub fn from_coarse_grid(
+        grid: &[f64],
+        level: usize,
+        noise: f64,
+        decay: f64,
+        seed: i64,
+    ) -> Result<Self, Box<dyn Error>> {
+        if level >= 31 {
+            return Err(format!("a grid must be below level 31, not {level}").into());
+        }
+        let side = grid_side::<N>(grid, level)?;
+        let grid = mirror_grid::<N>(grid, side);
+        let (level, side) = (level + 1, side * 2);
+        let mut result = Self::new(noise, decay, seed);
+        let size = cell_size(level);
+        result.values.clear();
+        result
+            .values
+            .extend(grid.iter().enumerate().map(|(index, &height)| {
+                let point = grid_coords::<N>(index, side).map(|c| c as u32 * size);
+                (point, height)
+            }));
+        result.coarse_extrema = grid_extrema::<N>(&grid, level);
+        result.coarse_level = level;
+        result.iterations = level;
+        Ok(result)
+    }
+
+    pub fn with_bound_kind(mut self, kind: BoundKind) -> Self {
+        self.bound_kind = kind;
+        self.update_bounds();
+        self
+    }
+
+    /// Modulates every displacement from here on, so this belongs before any points are generated.
+    pub fn with_modulation(mut self, modulation: Modulation<N>) -> Self {
+        self.modulation = Some(modulation);
+        self.reconfigure();
+        self
+    }
+
+    /// Transforms the displacements of every level, see [`FractalNoise::with_level_transform`].
+    pub fn with_transform(mut self, transform: DisplacementTransform) -> Self {
+        self.transforms = [transform; 32];
+        self.reconfigure();
+        self
+    }
+
+    /// Transforms the displacements of `level`, which belongs before any points are generated.
+    pub fn with_level_transform(
+        mut self,
+        level: usize,
+        transform: DisplacementTransform,
+    ) -> Result<Self, Box<dyn Error>> {
+        let slot = self
+            .transforms
+            .get_mut(level)
+            .ok_or_else(|| format!("level {level} is past the last of 32 levels"))?;
+        *slot = transform;
+        self.reconfigure();
+        Ok(self)
+    }
+
+    pub fn transform(&self, level: usize) -> DisplacementTransform {
+        self.transforms[level]
+    }
+
+    fn reconfigure(&mut self) {
+        if self.pins.is_empty() {
+            self.update_bounds();
+        } else {
+            // the variances the pins were solved with do not depend on modulations or transforms
+            self.condition()
+                .expect("pins stay solvable once they are placed");
+        }
+    }
+
+    pub fn modulation(&self) -> Option<&Modulation<N>> {
+        self.modulation.as_ref()
+    }
+
+    /// The furthest any point created at `level` can land from the average of its parents.
+    fn max_displacement(&self, level: usize) -> f64 {
+        let modulation = self.modulation.as_ref().map_or(1.0, Modulation::max);
+        self.noise[level] * 0.5 * modulation
+            + self.transforms[level].max_shift()
+            + self.level_adjustments[level]
+    }
+
+    fn update_bounds(&mut self) {
+        let displacements = array::from_fn(|level| self.max_displacement(level));
+        self.displacement_bounds = displacement_bounds::<N>(self.bound_kind, &displacements);
+    }
+
+    /// Pins `point` to `height`, conditioning the whole field to pass through it.
+    ///
+    /// The displacement of every ancestor of the pinned points is shifted by its share of the
+    /// conditional expectation given all pins, weighing each ancestor by how much of it reaches
+    /// each pin through the averaging and by the variance of its level. Along a line this is
+    /// exactly a Brownian bridge between the pins; elsewhere the conditioning falls off with the
+    /// shared ancestry. Pins must be placed before anything but the root has been generated, and
+    /// hold to within floating point rounding as long as no displacement depends on the heights,
+    /// as with terraces or a [`Modulation`] of the parents' average. A pin that the noise cannot
+    /// move independently of the others is refused, leaving the earlier pins in place.
+    pub fn pin(&mut self, point: [u32; N], height: f64) -> Result<(), Box<dyn Error>> {
+        if self.values.len() > 1 || self.iterations > 0 {
+            return Err("pins must be placed before any points are generated".into());
+        }
+        let previous = self.pins.clone();
+        match self.pins.iter_mut().find(|(p, _)| *p == point) {
+            Some(pin) => pin.1 = height,
+            None => self.pins.push((point, height)),
+        }
+        self.condition().inspect_err(|_| self.pins = previous)
+    }
+
+    pub fn pins(&self) -> &[([u32; N], f64)] {
+        &self.pins
+    }
+
+    fn condition(&mut self) -> Result<(), Box<dyn Error>> {
+        let root = self.bounds[0] / 2.0;
+        let mut free = self.clone();
+        free.adjustments.clear();
+        free.values.clear();
+        free.values.insert([0u32; N], root);
+
+        // how much of each ancestor's displacement reaches each pin, and its variance
+        let mut variances = HashMap::with_hasher(BuildHasherDefault::<HighwayHasher>::default());
+        let mut weights = Vec::with_capacity(self.pins.len());
+        let mut residuals = Vec::with_capacity(self.pins.len());
+        for &(point, height) in &self.pins {
+            let chain = free.explain_point(point);
+            let mut weight = HashMap::with_hasher(BuildHasherDefault::<HighwayHasher>::default());
+            weight.insert(point, 1.0);
+            for link in &chain {
+                let share = weight.get(&link.point).copied().unwrap_or(0.0);
+                let noise = match link.derivation {
+                    Some(derivation) => {
+                        for parent in derivation.parents {
+                            *weight.entry(parent).or_insert(0.0) += share * 0.5;
+                        }
+                        derivation.noise
+                    }
+                    None => free.noise(0),
+                };
+                variances.insert(link.point, noise * noise);
+            }
+            residuals.push(height - chain[0].value);
+            weights.push(weight);
+        }
+
+        let covariance = weights
+            .iter()
+            .map(|wi| {
+                weights
+                    .iter()
+                    .map(|wj| {
+                        wi.iter()
+                            .filter_map(|(a, w)| wj.get(a).map(|v| w * v * variances[a]))
+                            .sum::<f64>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let lambda = solve(covariance, residuals)
+            .map_err(|_| "the pins depend on each other or on levels without noise")?;
+
+        self.adjustments.clear();
+        for (weight, lambda) in weights.iter().zip(&lambda) {
+            for (&ancestor, w) in weight {
+                *self.adjustments.entry(ancestor).or_insert(0.0) +=
+                    w * variances[&ancestor] * lambda;
+            }
+        }
+        let root_adjustment = self.adjustments.remove(&[0u32; N]).unwrap_or(0.0);
+        self.values.insert([0u32; N], root + root_adjustment);
+
+        self.level_adjustments = [0.0; 32];
+        for (point, adjustment) in &self.adjustments {
+            if let Some(level) = creation_level(*point) {
+                let max = &mut self.level_adjustments[level];
+                *max = max.max(adjustment.abs());
+            }
+        }
+        self.update_bounds();
+        Ok(())
+    }
+
+    /// Every value lies within this range.
+    pub fn height_bounds(&self) -> RangeInclusive<f64> {
+        let root = self.values[&[0u32; N]];
+        let (lowest, highest) = self.coarse_extrema.first().map_or((root, root), |e| e[0]);
+        let spread = (self.coarse_level..32)
+            .map(|level| self.max_displacement(level))
+            .sum::<f64>();
+        (lowest - spread)..=(highest + spread)
+    }
+
+    fn derive(
+        &self,
+        target: [u32; N],
+        f: [u32; N],
+        s: [u32; N],
+        f_val: f64,
+        s_val: f64,
+        noise: f64,
+    ) -> f64 {
+        let (base, displacement) = self.displace(target, f, s, f_val, s_val, noise);
+        base + displacement + self.adjustments.get(&target).copied().unwrap_or(0.0)
+    }
+
+    /// The height `target` is displaced from and its transformed, modulated displacement.
+    fn displace(
         &self,
-        start: [u32; N],
+        target: [u32; N],
+        f: [u32; N],
+        s: [u32; N],
+        f_val: f64,
+        s_val: f64,
         noise: f64,
-    ) -> impl Iterator<Item = ([u32; N], f64)> + use<'_, N> {
-        let midpoint = 1u32.reverse_bits() >> self.iterations;
-        let base = self.values[&start];
-        iter::once((start, base)).chain((1..(1 << N)).map(move |combo| {
-            let mut target = start;
-            target
-                .iter_mut()
-                .zip(0..N)
-                .for_each(|(n, o)| *n = n.add(midpoint * (combo >> o & 1)));
-            let mut s = target;
-            s.iter_mut()
-                .zip(0..N)
-                .for_each(|(n, o)| *n = n.overflowing_add(midpoint * (combo >> o & 1)).0);
-            let f_val = self.values[&start];
-            let s_val = self.values[&s];
-
-            let computed = compute_midpoint(start, s, f_val, s_val, noise, self.seed);
-            (target, computed)
-        }))
+    ) -> (f64, f64) {
+        let average = (f_val + s_val) * 0.5;
+        let transform =
+            creation_level(target).map_or_else(Default::default, |l| self.transforms[l]);
+        let displacement = transform.displace(sample_displacement(f, s, noise, self.seed), noise);
+        (
+            transform.base(average),
+            displacement * self.modulation_at(target, average),
+        )
+    }
+
+    fn modulation_at(&self, target: [u32; N], average: f64) -> f64 {
+        self.modulation
+            .as_ref()
+            .map_or(1.0, |modulation| modulation.factor(target, average))
+    }
+
+    pub fn with_subdivision(mut self, subdivision: Subdivision) -> Self {
+        self.subdivision = subdivision;
+        self
     }
 
     pub fn step_midpoints(&mut self) -> Result<bool, Box<dyn Error>> {
+        if self.iterations >= self.noise.len() {
+            return Ok(false);
+        }
         let noise = self.noise(self.iterations);
         if noise.abs_diff_eq(&0.0, EPSILON) {
             return Ok(false);
         }
 
-        let next_values = self
-            .values
-            .keys()
-            .copied()
-            .flat_map(|start| self.next_points(start, noise))
-            .collect();
-        self.values = next_values;
-
+        self.refine(self.iterations);
         self.iterations += 1;
         Ok(true)
     }
@@ -126,6 +567,31 @@ impl<const N: usize> FractalNoise<N> {
         self.values
     }
 
+    /// The cached points in `order`, optionally only those created at `level`, as in
+    /// [`Derivation::level`], and those inside `region`. The root is created at no level, so
+    /// restricting to a level leaves it out.
+    pub fn ordered_values(
+        &self,
+        order: PointOrder,
+        level: Option<usize>,
+        region: Option<&[RangeInclusive<u32>; N]>,
+    ) -> vec::IntoIter<([u32; N], f64)> {
+        let mut values = self
+            .values
+            .iter()
+            .map(|(&point, &value)| (point, value))
+            .filter(|(point, _)| level.is_none_or(|level| creation_level(*point) == Some(level)))
+            .filter(|(point, _)| {
+                region.is_none_or(|region| region.iter().zip(point).all(|(r, n)| r.contains(n)))
+            })
+            .collect::<Vec<_>>();
+        match order {
+            PointOrder::Morton => values.sort_unstable_by(|(a, _), (b, _)| morton_cmp(a, b)),
+            PointOrder::RowMajor => values.sort_unstable_by_key(|(point, _)| *point),
+        }
+        values.into_iter()
+    }
+
     pub fn iterations(&self) -> usize {
         self.iterations
     }
@@ -146,424 +612,2044 @@ impl<const N: usize> FractalNoise<N> {
         self.bounds[iterations]
     }
 
-    #[cfg(not(nightly))]
+    pub fn bound_kind(&self) -> BoundKind {
+        self.bound_kind
+    }
+
+    pub fn subdivision(&self) -> Subdivision {
+        self.subdivision
+    }
+
+    /// How far any point created at `iterations` or later may stray from the corners of its cell.
+    pub fn displacement_bound(&self, iterations: usize) -> f64 {
+        self.displacement_bounds[iterations]
+    }
+
+    /// With [`Subdivision::Simplex`], the bounds only cover the simplex of each cell containing
+    /// `point`, see [`CellBounds::simplex`].
     pub fn cached_bounds_for(
         &mut self,
         point: [u32; N],
         height: f64,
         iterations: usize,
-    ) -> (bool, RangeInclusive<f64>, [u32; N], usize, u32) {
-        self.cached_bounds_for_inner::<Vec<([u32; N], f64)>>(point, height, iterations)
+    ) -> CellBounds<N> {
+        self.cached_bounds_with(point, height, iterations, self.subdivision)
     }
 
-    #[cfg(nightly)]
-    pub fn cached_bounds_for(
+    /// Like [`FractalNoise::cached_bounds_for`], but always covering whole cells.
+    fn cached_cube_bounds_for(
         &mut self,
         point: [u32; N],
         height: f64,
         iterations: usize,
-    ) -> (bool, RangeInclusive<f64>, [u32; N], usize, u32)
-    where
-        [(); 1 << N]:,
-    {
-        self.cached_bounds_for_inner::<[([u32; N], f64); 1 << N]>(point, height, iterations)
-    }
-
-    fn lookup_or_compute(&mut self, midpoint: u32, target: [u32; N], noise: f64) -> f64 {
-        if let Some(&existing) = self.values.get(&target) {
-            existing
-        } else {
-            let nextpoint = midpoint << 1;
-            let f = target.map(|v| v.div(&midpoint).div(&2).mul(nextpoint));
-            let f_val = self.values[&f];
-            let mut s = f;
-            s.iter_mut()
-                .zip(f)
-                .zip(target)
-                .for_each(|((second, first), target)| {
-                    *second = second
-                        .overflowing_add((target - first).overflowing_shl(1).0)
-                        .0;
-                });
-            let s_val = self.values[&s];
-
-            let computed = compute_midpoint(f, s, f_val, s_val, noise, self.seed);
-            self.values.insert(target, computed);
-            computed
-        }
+    ) -> CellBounds<N> {
+        self.cached_bounds_with(point, height, iterations, Subdivision::Cube)
     }
 
-    fn cached_bounds_for_inner<PA: ValidPointsArray<([u32; N], f64), N>>(
+    fn cached_bounds_with(
         &mut self,
         point: [u32; N],
         height: f64,
-        mut iterations: usize,
-    ) -> (bool, RangeInclusive<f64>, [u32; N], usize, u32) {
-        let mut last_bound = f64::NEG_INFINITY..=f64::INFINITY;
-
-        let mut midpoint = 1u32.reverse_bits() >> iterations;
+        iterations: usize,
+        subdivision: Subdivision,
+    ) -> CellBounds<N> {
+        with_corners!(
+            f64,
+            self.walk_bounds(
+                point,
+                height,
+                iterations,
+                subdivision,
+                |noise, corners, level, simplex| {
+                    let (minpoint, maxpoint) = corners.iter().fold(
+                        (f64::INFINITY, f64::NEG_INFINITY),
+                        |(fmin, fmax), &(_, f)| (fmin.min(f), fmax.max(f)),
+                    );
+                    noise.bounds_around(corners[0].0, level, minpoint, maxpoint, simplex)
+                }
+            )
+        )
+    }
 
-        let nextpoint = midpoint.overflowing_shl(1).0;
-        let mut points = if nextpoint == 0 {
-            PA::init(iter::once(([0u32; N], self.values[&[0u32; N]])))
-        } else {
-            let mut next = point;
+    /// The bounds of the cell containing `point` at `level`, computing its corners if needed.
+    pub fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        self.cell_bounds_with(point, level, self.subdivision)
+    }
 
-            next.iter_mut().for_each(|n| {
-                *n = n
-                    .checked_div(nextpoint)
-                    .map(|n| n.mul(&nextpoint))
-                    .unwrap_or(0)
-            });
-            PA::init((0..(1 << N)).map(|combo| {
-                let mut other = next;
+    fn cell_bounds_with(
+        &mut self,
+        point: [u32; N],
+        level: usize,
+        subdivision: Subdivision,
+    ) -> CellBounds<N> {
+        let size = cell_size(level);
+        let base = point.map(|n| n.checked_div(size).map_or(0, |n| n.mul(size)));
+        let simplex = (subdivision == Subdivision::Simplex).then(|| simplex_axes(point, base));
+        let (minpoint, maxpoint) = corner_combos(simplex)
+            .map(|combo| {
+                let mut other = base;
                 other
                     .iter_mut()
                     .zip(0..N)
-                    .for_each(|(n, o)| *n = n.overflowing_add(nextpoint * (combo >> o & 1)).0);
-                (other, *self.values.get(&other).unwrap())
-            }))
+                    .for_each(|(n, o)| *n = n.overflowing_add(size * (combo >> o & 1)).0);
+                self.find_point(other)
+            })
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(fmin, fmax), f| {
+                (fmin.min(f), fmax.max(f))
+            });
+        self.bounds_around(base, level, minpoint, maxpoint, simplex)
+    }
+
+    /// Walks the cells containing `point` from the root down to the first terminated one.
+    pub fn bounds_hierarchy(&mut self, point: [u32; N]) -> BoundsHierarchy<'_, N> {
+        BoundsHierarchy {
+            noise: self,
+            point,
+            level: Some(0),
+        }
+    }
+
+    fn bounds_around(
+        &mut self,
+        base: [u32; N],
+        level: usize,
+        minpoint: f64,
+        maxpoint: f64,
+        simplex: Option<[usize; N]>,
+    ) -> CellBounds<N> {
+        if let Some(extrema) = self.coarse_extrema.get(level) {
+            // the imported heights inside the cell, and whatever is generated below them
+            let (lowest, highest) = extrema[cell_index(base, level)];
+            let bound = self
+                .displacement_bounds
+                .get(self.coarse_level)
+                .copied()
+                .unwrap_or(0.0);
+            return CellBounds {
+                terminated: false,
+                heights: (lowest - bound)..=(highest + bound),
+                base,
+                level,
+                simplex,
+            };
+        }
+        let (terminated, bound) = if level < 32 {
+            // a modulation can scale the displacements past the noise of the level
+            let scale = self.modulation.as_ref().map_or(1.0, Modulation::max);
+            (
+                (self.noise(level) * scale).abs_diff_eq(&0.0, EPSILON),
+                self.displacement_bound(level),
+            )
+        } else {
+            (true, 0.0)
         };
+        CellBounds {
+            terminated,
+            heights: (minpoint - bound)..=(maxpoint + bound),
+            base,
+            level,
+            simplex,
+        }
+    }
 
-        while 0 < midpoint {
-            let noise = self.noise(iterations);
-            if noise.abs_diff_eq(&0.0, EPSILON) {
-                return (true, last_bound, points[0].0, iterations, midpoint << 1);
-            }
+    /// Explains `point` and every ancestor it was derived from, finest first and ending at the root
+    /// or the imported points of [`FractalNoise::from_coarse_grid`].
+    pub fn explain_point(&mut self, point: [u32; N]) -> Vec<PointProvenance<N>> {
+        self.find_point(point);
 
-            let (minpoint, maxpoint) = points
-                .as_ref()
-                .iter()
-                .map(|(_, v)| *v)
-                .fold((f64::INFINITY, f64::NEG_INFINITY), |(fmin, fmax), f| {
-                    (fmin.min(f), fmax.max(f))
+        let mut pending = vec![point];
+        let mut seen = HashMap::with_hasher(BuildHasherDefault::<HighwayHasher>::default());
+        while let Some(next) = pending.pop() {
+            if seen.contains_key(&next) {
+                continue;
+            }
+            let value = self.values[&next];
+            let derivation = creation_level(next)
+                .filter(|&level| level >= self.coarse_level)
+                .map(|level| {
+                    let (f, s) = parents_of(next, 1u32.reverse_bits() >> level);
+                    pending.extend([f, s]);
+                    let noise = self.noise(level);
+                    let parent_values = [self.values[&f], self.values[&s]];
+                    let average = (parent_values[0] + parent_values[1]) * 0.5;
+                    let (base, displacement) =
+                        self.displace(next, f, s, parent_values[0], parent_values[1], noise);
+                    Derivation {
+                        level,
+                        noise,
+                        parents: [f, s],
+                        parent_values,
+                        base,
+                        displacement,
+                        modulation: self.modulation_at(next, average),
+                        conditioning: self.adjustments.get(&next).copied().unwrap_or(0.0),
+                    }
                 });
+            seen.insert(
+                next,
+                PointProvenance {
+                    point: next,
+                    value,
+                    derivation,
+                },
+            );
+        }
 
-            let bound = self.upper_bound(iterations);
-            last_bound = (minpoint - bound)..=(maxpoint + bound);
+        let mut chain = seen.into_values().collect::<Vec<_>>();
+        chain.sort_by_key(|p| {
+            (
+                p.point != point,
+                Reverse(p.derivation.map_or(0, |d| d.level + 1)),
+                p.point,
+            )
+        });
+        chain
+    }
 
-            if !last_bound.contains(&height) {
-                return (false, last_bound, points[0].0, iterations, midpoint << 1);
-            }
+    pub fn find_point(&mut self, n: [u32; N]) -> f64 {
+        with_corners!(f64, self.walk_from(n, 0))
+    }
 
-            // compute the next starting point
-            let mut next = point;
-            next.iter_mut()
-                .zip(points[0].0)
-                .for_each(|(n, p)| *n = (*n - p).div(&midpoint).mul(&midpoint).add(p));
-            points = PA::init((0..(1 << N)).map(|combo| {
-                let mut other = next;
-                other
-                    .iter_mut()
-                    .zip(0..N)
-                    .for_each(|(n, o)| *n = n.overflowing_add(midpoint * (combo >> o & 1)).0);
-                (other, self.lookup_or_compute(midpoint, other, noise))
-            }));
-            self.values.extend(points.as_ref().iter().copied());
+    /// Finds every point in `points`, walking them in Morton order so that each walk starts from
+    /// the deepest cell shared with the previous point rather than from the root.
+    pub fn find_points(&mut self, points: &[[u32; N]]) -> Vec<f64> {
+        let mut order = (0..points.len()).collect::<Vec<_>>();
+        order.sort_unstable_by(|&a, &b| morton_cmp(&points[a], &points[b]));
 
-            midpoint >>= 1;
-            iterations += 1;
+        let mut found = vec![0.0; points.len()];
+        let mut previous = None;
+        for i in order {
+            let n = points[i];
+            let level = previous.map_or(0, |previous| shared_level(previous, n));
+            found[i] = with_corners!(f64, self.walk_from(n, level));
+            previous = Some(n);
         }
-        (true, last_bound, points[0].0, iterations, 1)
+        found
     }
+}
 
-    #[cfg(not(nightly))]
-    pub fn find_point(&mut self, n: [u32; N]) -> f64 {
-        self.find_point_inner::<Vec<([u32; N], f64)>>(n)
+impl<const N: usize> Lattice<N> for FractalNoise<N> {
+    type Value = f64;
+
+    fn cache(&self) -> &HashMap<[u32; N], f64> {
+        &self.values
+    }
+
+    fn cache_mut(&mut self) -> &mut HashMap<[u32; N], f64> {
+        &mut self.values
+    }
+
+    fn walk_subdivision(&self) -> Subdivision {
+        self.subdivision
+    }
+
+    fn derive_at(
+        &self,
+        target: [u32; N],
+        f: [u32; N],
+        s: [u32; N],
+        f_val: f64,
+        s_val: f64,
+        level: usize,
+    ) -> f64 {
+        self.derive(target, f, s, f_val, s_val, self.noise[level])
     }
 
-    #[cfg(nightly)]
-    pub fn find_point(&mut self, n: [u32; N]) -> f64
-    where
-        [(); 1 << N]:,
-    {
-        find_point_inner::<[([u32; N], f64); 1 << N]>(n)
+    fn walk(&mut self, n: [u32; N]) -> f64 {
+        self.find_point(n)
+    }
+}
+
+/// A cache of lattice values filled in by midpoint displacement. The walks down to a point are the
+/// same whatever each point holds, be it one height or several channels, and whether cells are
+/// cubes or boxes.
+trait Lattice<const N: usize> {
+    type Value: Copy;
+
+    fn cache(&self) -> &HashMap<[u32; N], Self::Value>;
+
+    fn cache_mut(&mut self) -> &mut HashMap<[u32; N], Self::Value>;
+
+    fn walk_subdivision(&self) -> Subdivision;
+
+    /// The side lengths of the cells at `level`, which halve along every axis at every level
+    /// unless overridden.
+    fn walk_size(&self, level: usize) -> [u64; N] {
+        [1 << (32 - level.min(32)); N]
     }
 
-    fn find_point_inner<PA: ValidPointsArray<([u32; N], f64), N>>(&mut self, n: [u32; N]) -> f64 {
+    /// The value of `target`, created at `level` between its parents `f` and `s`.
+    fn derive_at(
+        &self,
+        target: [u32; N],
+        f: [u32; N],
+        s: [u32; N],
+        f_val: Self::Value,
+        s_val: Self::Value,
+        level: usize,
+    ) -> Self::Value;
+
+    /// [`Lattice::walk_from`] the root, with a buffer fitting the corners of a cell.
+    fn walk(&mut self, n: [u32; N]) -> Self::Value;
+
+    /// The value at `n`, walking down from its cell at `level`.
+    fn walk_from<PA: ValidPointsArray<([u32; N], Self::Value), N>>(
+        &mut self,
+        n: [u32; N],
+        level: usize,
+    ) -> Self::Value {
         // fast-track: maybe we have this computed
-        if let Some(&v) = self.values.get(&n) {
+        if let Some(&v) = self.cache().get(&n) {
             return v;
         }
 
-        let mut midpoint = 1u32.reverse_bits();
-        let mut base = ([0u32; N], *self.values.get(&[0u32; N]).unwrap());
-        for iterations in 0.. {
+        let start = box_base(n, self.walk_size(level));
+        let start_val = match self.cache().get(&start) {
+            Some(&v) => v,
+            None => self.walk(start),
+        };
+        let subdivision = self.walk_subdivision();
+        let mut base = (start, start_val);
+        for level in level.. {
             if base.0 == n {
                 return base.1;
             }
 
-            // compute the next starting point
-            let mut next = n;
-            next.iter_mut()
-                .zip(base.0)
-                .for_each(|(n, p)| *n = (*n - p).div(&midpoint).mul(&midpoint).add(p));
-            let noise = self.noise(iterations);
-            let points = PA::init((0..(1 << N)).map(|combo| {
-                let mut other = next;
-                other
-                    .iter_mut()
-                    .zip(0..N)
-                    .for_each(|(n, o)| *n = n.overflowing_add(midpoint * (combo >> o & 1)).0);
-                (other, self.lookup_or_compute(midpoint, other, noise))
+            let half = self.walk_size(level + 1);
+            let next = box_base(n, half);
+            let simplex = (subdivision == Subdivision::Simplex).then(|| simplex_axes(n, next));
+            let points = PA::init(corner_combos(simplex).map(|combo| {
+                let other = box_offset(next, half, combo);
+                (other, self.lookup_or_compute(other, level))
             }));
             base = points[0];
+        }
+        unreachable!("We must have computed n by now");
+    }
 
-            midpoint >>= 1;
+    /// The value of `target`, created at `level`.
+    fn lookup_or_compute(&mut self, target: [u32; N], level: usize) -> Self::Value {
+        if let Some(&existing) = self.cache().get(&target) {
+            return existing;
         }
+        let (f, s) = box_parents(target, self.walk_size(level), self.walk_size(level + 1));
+        // parents are usually left behind by the walk down, unless it started partway
+        let f_val = self
+            .cache()
+            .get(&f)
+            .copied()
+            .unwrap_or_else(|| self.walk(f));
+        let s_val = self
+            .cache()
+            .get(&s)
+            .copied()
+            .unwrap_or_else(|| self.walk(s));
 
-        // last chance
-        if let Some(&v) = self.values.get(&n) {
-            return v;
+        let computed = self.derive_at(target, f, s, f_val, s_val, level);
+        self.cache_mut().insert(target, computed);
+        computed
+    }
+
+    /// Walks the cells containing `point` down from `level`, taking the `bounds` of the corners
+    /// of each, until they are terminated or exclude `height`.
+    fn walk_bounds<PA: ValidPointsArray<([u32; N], Self::Value), N>>(
+        &mut self,
+        point: [u32; N],
+        height: f64,
+        mut level: usize,
+        subdivision: Subdivision,
+        mut bounds: impl FnMut(
+            &mut Self,
+            &[([u32; N], Self::Value)],
+            usize,
+            Option<[usize; N]>,
+        ) -> CellBounds<N>,
+    ) -> CellBounds<N> {
+        let simplex_for =
+            |base| (subdivision == Subdivision::Simplex).then(|| simplex_axes(point, base));
+
+        let mut simplex = None;
+        let mut points = if level == 0 {
+            // every corner of the root cell wraps around to the origin
+            PA::init(iter::once(([0u32; N], self.cache()[&[0u32; N]])))
+        } else {
+            let size = self.walk_size(level);
+            let next = box_base(point, size);
+            simplex = simplex_for(next);
+            PA::init(corner_combos(simplex).map(|combo| {
+                let other = box_offset(next, size, combo);
+                (other, self.walk(other))
+            }))
+        };
+
+        loop {
+            let found = bounds(self, points.as_ref(), level, simplex);
+            if found.terminated() || !found.contains(height) {
+                return found;
+            }
+
+            let half = self.walk_size(level + 1);
+            let next = box_base(point, half);
+            simplex = simplex_for(next);
+            points = PA::init(corner_combos(simplex).map(|combo| {
+                let other = box_offset(next, half, combo);
+                (other, self.lookup_or_compute(other, level))
+            }));
+
+            level += 1;
         }
-        unreachable!("We must have computed n by now");
+    }
+
+    /// Creates every point of `level` in the cells whose base is cached; lazily computed points
+    /// off the lattice of `level` are kept, but not expanded.
+    fn refine(&mut self, level: usize) {
+        let (size, half) = (self.walk_size(level), self.walk_size(level + 1));
+        // only the axes halved at this level gain points
+        let split = (0..N).fold(0, |split, axis| {
+            split | ((half[axis] < size[axis]) as u32) << axis
+        });
+        let this = &*self;
+        let next_values = this
+            .cache()
+            .keys()
+            .copied()
+            .filter(|&start| box_base(start, size) == start)
+            .flat_map(|start| {
+                (1..(1 << N))
+                    .filter(move |combo| combo & !split == 0)
+                    .map(move |combo| {
+                        let target = box_offset(start, half, combo);
+                        let s = box_offset(target, half, combo);
+                        let (f_val, s_val) = (this.cache()[&start], this.cache()[&s]);
+                        (
+                            target,
+                            this.derive_at(target, start, s, f_val, s_val, level),
+                        )
+                    })
+            })
+            .collect::<Vec<_>>();
+        self.cache_mut().extend(next_values);
     }
 }
 
-pub fn compute_midpoint<const N: usize>(
-    i1: [u32; N],
-    i2: [u32; N],
-    v1: f64,
-    v2: f64,
-    noise: f64,
-    seed: i64,
-) -> f64 {
+pub fn compute_midpoint<P: Hash>(i1: P, i2: P, v1: f64, v2: f64, noise: f64, seed: i64) -> f64 {
+    (v1 + v2) * 0.5 + sample_displacement(i1, i2, noise, seed)
+}
+
+/// The displacement added to the average of `i1` and `i2` by [`compute_midpoint`].
+pub fn sample_displacement<P: Hash>(i1: P, i2: P, noise: f64, seed: i64) -> f64 {
+    if noise == 0.0 {
+        // nothing left to displace by, and the remainder below would be NaN
+        return 0.0;
+    }
     let mut hasher = HighwayHasher::default();
     hasher.write_i64(seed);
     i1.hash(&mut hasher);
     i2.hash(&mut hasher);
-    let sampled = (hasher.finish() as u32 as f64 % noise) - noise * 0.5;
-
-    (v1 + v2) * 0.5 + sampled
+    (hasher.finish() as u32 as f64 % noise) - noise * 0.5
 }
 
-trait ValidPointsArray<T, const N: usize>: IndexMut<usize, Output = T> + AsRef<[T]> {
-    fn init(source: impl Iterator<Item = T>) -> Self;
+/// The side length of a cell at `level`, where the root cell's `1 << 32` wraps to zero.
+fn cell_size(level: usize) -> u32 {
+    1u64.checked_shl(32 - level as u32).unwrap_or(0) as u32
 }
 
-impl<T, const N: usize> ValidPointsArray<T, N> for Vec<T> {
-    fn init(source: impl Iterator<Item = T>) -> Self {
-        source.collect()
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellBounds<const N: usize> {
+    terminated: bool,
+    heights: RangeInclusive<f64>,
+    base: [u32; N],
+    level: usize,
+    simplex: Option<[usize; N]>,
 }
 
-#[cfg(nightly)]
-impl<T, const N: usize> ValidPointsArray<T, N> for [T; 1 << N]
-where
-    [T; 1 << N]:,
-    T: Copy,
-{
-    fn init(mut source: impl Iterator<Item = T>) -> Self {
-        let next = source.next().unwrap();
-        let mut result = [next; 1 << N];
-        result[1..].iter_mut().zip(source).for_each(|(r, s)| *r = s);
-        result
+impl<const N: usize> CellBounds<N> {
+    /// The bounds of the cell at `level` containing `point`, for implementing
+    /// [`NoiseField`] on other fields.
+    pub fn new(
+        point: [u32; N],
+        level: usize,
+        heights: RangeInclusive<f64>,
+        terminated: bool,
+    ) -> Self {
+        let size = cell_size(level);
+        Self {
+            terminated,
+            heights,
+            base: point.map(|n| n.checked_div(size).map_or(0, |n| n.mul(size))),
+            level,
+            simplex: None,
+        }
     }
-}
 
-#[derive(Clone, Copy)]
-struct RectangularPrism {
-    lower: Point3<f64>,
-    upper: Point3<f64>,
-}
+    /// Whether no displacement larger than the tolerance remains below this cell.
+    pub fn terminated(&self) -> bool {
+        self.terminated
+    }
 
-impl RectangularPrism {
-    fn around(base: [u32; 2], cache: &mut FractalNoise<2>, nextpoint: u32) -> Self {
-        Self::new(
-            [
-                [0, 0],
-                [0, nextpoint],
-                [nextpoint, 0],
-                [nextpoint, nextpoint],
-            ]
-            .into_iter()
-            .map(|[offset_x, offset_z]| {
-                let [x, z] = [
-                    base[0].overflowing_add(offset_x).0,
-                    base[1].overflowing_add(offset_z).0,
-                ];
-                let y = cache.find_point([x, z]);
-                Point3::new(x as f64, y, z as f64)
-            }),
-        )
+    pub fn heights(&self) -> RangeInclusive<f64> {
+        self.heights.clone()
     }
 
-    fn new(mut source: impl Iterator<Item = Point3<f64>>) -> Self {
-        let mut lower = source.next().unwrap();
-        let mut upper = lower;
-        for next in source {
-            lower = lower.zip(next, |p1, p2| p1.min(p2));
-            upper = upper.zip(next, |p1, p2| p1.max(p2));
-        }
-        Self { lower, upper }
+    pub fn base(&self) -> [u32; N] {
+        self.base
     }
 
-    fn intersect(&self, ray: &Ray) -> Option<(f64, f64)> {
-        let t1 = (self.lower.x - ray.origin.x) * ray.inv_dir.x;
-        let t2 = (self.upper.x - ray.origin.x) * ray.inv_dir.x;
-        let t3 = (self.lower.y - ray.origin.y) * ray.inv_dir.y;
-        let t4 = (self.upper.y - ray.origin.y) * ray.inv_dir.y;
-        let t5 = (self.lower.z - ray.origin.z) * ray.inv_dir.z;
-        let t6 = (self.upper.z - ray.origin.z) * ray.inv_dir.z;
+    pub fn level(&self) -> usize {
+        self.level
+    }
 
-        let tmin = t1.min(t2).max(t3.min(t4)).max(t5.min(t6));
-        let tmax = t1.max(t2).min(t3.max(t4)).min(t5.max(t6));
+    pub fn size(&self) -> u64 {
+        1 << (32 - self.level)
+    }
 
-        if tmax >= 0.0 {
-            if tmin <= tmax {
-                return Some((tmin, tmax));
-            }
+    pub fn aabb(&self) -> CellAabb<N> {
+        let size = self.size() as f64;
+        CellAabb {
+            lower: self.base.map(|n| n as f64),
+            upper: self.base.map(|n| n as f64 + size),
+            heights: self.heights(),
         }
-        None
     }
 
-    fn contains(&self, point: Point3<f64>) -> bool {
-        self.lower.x < point.x
-            && self.lower.y < point.y
-            && self.lower.z < point.z
-            && point.x < self.upper.x
-            && point.z < self.upper.z
+    pub fn contains(&self, height: f64) -> bool {
+        self.heights.contains(&height)
+    }
+
+    /// If these bounds only cover one simplex of the cell, the axes in the order its vertices
+    /// step along them from the base: the simplex holds the points whose offsets from the base
+    /// are ordered the same way.
+    pub fn simplex(&self) -> Option<[usize; N]> {
+        self.simplex
     }
 }
 
-#[derive(Copy, Clone)]
-pub struct Ray {
-    direction: Vector3<f64>,
-    inv_dir: Vector3<f64>,
-    origin: Point3<f64>,
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellAabb<const N: usize> {
+    pub lower: [f64; N],
+    pub upper: [f64; N],
+    pub heights: RangeInclusive<f64>,
 }
 
-impl Ray {
+pub struct BoundsHierarchy<'a, const N: usize> {
+    noise: &'a mut FractalNoise<N>,
+    point: [u32; N],
+    level: Option<usize>,
+}
+
+impl<const N: usize> Iterator for BoundsHierarchy<'_, N> {
+    type Item = CellBounds<N>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let level = self.level?;
+        let bounds = self.noise.cell_bounds(self.point, level);
+        self.level = (!bounds.terminated()).then_some(level + 1);
+        Some(bounds)
+    }
+}
+
+/// The furthest a point can rise above the corners of its cell when each remaining level can
+/// displace a point from the average of its parents by at most the matching `displacements`.
+fn dimensional_bound<const N: usize>(displacements: &[f64]) -> f64 {
+    match N {
+        0 => 0.0,
+        1 => {
+            // every midpoint is raised as far as possible, so descending towards the higher of the
+            // two new segments always dominates
+            let (mut high, mut low, mut furthest) = (0f64, 0f64, 0f64);
+            for &displacement in displacements {
+                let midpoint = (high + low) * 0.5 + displacement;
+                furthest = furthest.max(midpoint);
+                (high, low) = (high.max(low), midpoint);
+            }
+            furthest
+        }
+        // each new point averages two points of its cell, and with level corners some point on
+        // every level has both parents at the running maximum
+        _ => displacements.iter().sum(),
+    }
+}
+
+/// The bound of `kind` for each level, when each level can displace a point from the average of its
+/// parents by at most the matching `displacements`.
+fn displacement_bounds<const N: usize>(kind: BoundKind, displacements: &[f64; 32]) -> [f64; 32] {
+    level_bounds::<N>(kind, displacements).try_into().unwrap()
+}
+
+/// Like [`displacement_bounds`], for any number of levels.
+fn level_bounds<const N: usize>(kind: BoundKind, displacements: &[f64]) -> Vec<f64> {
+    match kind {
+        BoundKind::Geometric => {
+            let mut bounds = displacements.iter().map(|d| d * 2.0).collect::<Vec<_>>();
+            for i in (0..bounds.len().saturating_sub(1)).rev() {
+                bounds[i] += bounds[i + 1];
+            }
+            bounds
+        }
+        BoundKind::Dimensional => (0..displacements.len())
+            .map(|level| dimensional_bound::<N>(&displacements[level..]))
+            .collect(),
+    }
+}
+
+/// Solves `a * x = b` by Gaussian elimination with partial pivoting, failing if `a` is singular to
+/// within rounding.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Result<Vec<f64>, Box<dyn Error>> {
+    let n = b.len();
+    let scale = (0..n).map(|i| a[i][i].abs()).fold(0.0, f64::max);
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap();
+        if a[pivot][col].abs() <= scale * n as f64 * f64::EPSILON {
+            return Err("the system is singular".into());
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in (col + 1)..n {
+            let factor = a[row][col] / a[col][col];
+            let (pivot, rest) = a.split_at_mut(row);
+            for (x, p) in rest[0][col..].iter_mut().zip(&pivot[col][col..]) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let rest = ((row + 1)..n).map(|k| a[row][k] * x[k]).sum::<f64>();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    Ok(x)
+}
+
+/// The axes ordered by descending offset of `point` from `base`, ties broken by axis, which picks
+/// the Kuhn simplex of the cell at `base` containing `point`.
+fn simplex_axes<const N: usize>(point: [u32; N], base: [u32; N]) -> [usize; N] {
+    let mut axes = array::from_fn(|axis| axis);
+    axes.sort_unstable_by_key(|&axis| (Reverse(point[axis].wrapping_sub(base[axis])), axis));
+    axes
+}
+
+/// The corners of a cell as bitmasks of the axes stepped along, either every corner or only the
+/// vertices of `simplex`, base first.
+fn corner_combos<const N: usize>(simplex: Option<[usize; N]>) -> impl Iterator<Item = u32> {
+    let cube = simplex
+        .is_none()
+        .then_some(0..(1 << N))
+        .into_iter()
+        .flatten();
+    let simplex = simplex.into_iter().flat_map(|axes| {
+        (0..=N).map(move |k| axes[..k].iter().fold(0, |combo, axis| combo | 1 << axis))
+    });
+    cube.chain(simplex)
+}
+
+/// Orders points along the Z-order curve, interleaving bits with axis 0 the most significant.
+fn morton_cmp<const N: usize>(a: &[u32; N], b: &[u32; N]) -> Ordering {
+    let mut axis = 0;
+    let mut highest = 0u32;
+    for (i, (a, b)) in a.iter().zip(b).enumerate() {
+        let differs = a ^ b;
+        if highest < differs && highest < (highest ^ differs) {
+            axis = i;
+            highest = differs;
+        }
+    }
+    a[axis].cmp(&b[axis])
+}
+
+/// The deepest level whose cell holds both `a` and `b`.
+fn shared_level<const N: usize>(a: [u32; N], b: [u32; N]) -> usize {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a ^ b).leading_zeros() as usize)
+        .min()
+        .unwrap_or(32)
+        .min(31)
+}
+
+/// The level at which `point` is first created, or `None` for the root.
+fn creation_level<const N: usize>(point: [u32; N]) -> Option<usize> {
+    let zeros = point.iter().map(|c| c.trailing_zeros()).min().unwrap_or(32);
+    (zeros < 32).then(|| 31 - zeros as usize)
+}
+
+/// The coordinates of the `index`th point of a row-major grid with `side` points per axis.
+fn grid_coords<const N: usize>(mut index: usize, side: usize) -> [usize; N] {
+    let mut coords = [0; N];
+    for c in coords.iter_mut().rev() {
+        *c = index % side;
+        index /= side;
+    }
+    coords
+}
+
+/// The index of `coords` in a row-major grid with `side` points per axis.
+fn grid_index<const N: usize>(coords: [usize; N], side: usize) -> usize {
+    coords.iter().fold(0, |index, c| index * side + c)
+}
+
+/// `grid` with `side` points per axis followed by its mirror image along every axis, with twice
+/// the points per axis.
+fn mirror_grid<const N: usize>(grid: &[f64], side: usize) -> Vec<f64> {
+    let len = grid.len() << N;
+    (0..len)
+        .map(|index| {
+            let coords = grid_coords::<N>(index, side * 2).map(|c| c.min(side * 2 - 1 - c));
+            grid[grid_index(coords, side)]
+        })
+        .collect()
+}
+
+/// The number of points per axis of `grid`, if it has one for every corner of the cells at `level`.
+fn grid_side<const N: usize>(grid: &[f64], level: usize) -> Result<usize, Box<dyn Error>> {
+    let side = match level {
+        0..32 => 1usize << level,
+        _ => return Err(format!("a grid must be below level 32, not {level}").into()),
+    };
+    let len = (0..N).try_fold(1usize, |len, _| len.checked_mul(side));
+    if len != Some(grid.len()) {
+        return Err(format!("expected {side}^{N} heights, got {}", grid.len()).into());
+    }
+    Ok(side)
+}
+
+/// The extremes of `grid` over each closed cell above `level`, indexed by level and then by
+/// [`cell_index`], merged upwards from the cells at `level`.
+fn grid_extrema<const N: usize>(grid: &[f64], level: usize) -> Vec<Vec<(f64, f64)>> {
+    let side = 1usize << level;
+    let mut extrema = (0..grid.len())
+        .map(|index| {
+            let coords = grid_coords::<N>(index, side);
+            (0..1usize << N)
+                .map(|combo| {
+                    let corner: [usize; N] =
+                        array::from_fn(|a| (coords[a] + (combo >> a & 1)) % side);
+                    grid[grid_index(corner, side)]
+                })
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), h| {
+                    (lo.min(h), hi.max(h))
+                })
+        })
+        .collect::<Vec<_>>();
+    let mut levels = Vec::with_capacity(level);
+    for child_level in (1..=level).rev() {
+        let side = 1usize << (child_level - 1);
+        extrema = (0..extrema.len() >> N)
+            .map(|index| {
+                let coords = grid_coords::<N>(index, side);
+                (0..1usize << N)
+                    .map(|combo| {
+                        let child: [usize; N] =
+                            array::from_fn(|a| coords[a] * 2 + (combo >> a & 1));
+                        extrema[grid_index(child, side * 2)]
+                    })
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (l, h)| {
+                        (lo.min(l), hi.max(h))
+                    })
+            })
+            .collect();
+        levels.push(extrema.clone());
+    }
+    levels.reverse();
+    levels
+}
+
+/// The row-major index of the cell at `level` with corner `base` among all cells of that level.
+fn cell_index<const N: usize>(base: [u32; N], level: usize) -> usize {
+    let coords = base.map(|n| n.checked_shr(32 - level as u32).unwrap_or(0) as usize);
+    grid_index(coords, 1usize << level)
+}
+
+/// The two points averaged to create `target`, which sits `midpoint` away from its cell base.
+fn parents_of<const N: usize>(target: [u32; N], midpoint: u32) -> ([u32; N], [u32; N]) {
+    let nextpoint = midpoint.overflowing_shl(1).0;
+    let f = target.map(|v| v.checked_div(nextpoint).map_or(0, |v| v.mul(nextpoint)));
+    let mut s = f;
+    s.iter_mut()
+        .zip(f)
+        .zip(target)
+        .for_each(|((second, first), target)| {
+            *second = second
+                .overflowing_add((target - first).overflowing_shl(1).0)
+                .0;
+        });
+    (f, s)
+}
+
+/// `point` moved by `distance` along every axis set in `combo`, wrapping around.
+fn offset<const N: usize>(mut point: [u32; N], distance: u32, combo: u32) -> [u32; N] {
+    point
+        .iter_mut()
+        .zip(0..N)
+        .for_each(|(n, o)| *n = n.overflowing_add(distance * (combo >> o & 1)).0);
+    point
+}
+
+/// The base of the box `size` wide that contains `n`.
+fn box_base<const N: usize>(n: [u32; N], size: [u64; N]) -> [u32; N] {
+    array::from_fn(|axis| (n[axis] as u64 / size[axis] * size[axis]) as u32)
+}
+
+/// `point` moved by `size` along every axis set in `combo`, wrapping around.
+fn box_offset<const N: usize>(mut point: [u32; N], size: [u64; N], combo: u32) -> [u32; N] {
+    for (axis, n) in point.iter_mut().enumerate() {
+        if combo >> axis & 1 == 1 {
+            *n = n.wrapping_add(size[axis] as u32);
+        }
+    }
+    point
+}
+
+/// The two points averaged to create `target` inside a box `size` wide, which straddle it along
+/// every axis it is off the corners of the box, `half` away.
+fn box_parents<const N: usize>(
+    target: [u32; N],
+    size: [u64; N],
+    half: [u64; N],
+) -> ([u32; N], [u32; N]) {
+    let (mut f, mut s) = (target, target);
+    for axis in 0..N {
+        if !(target[axis] as u64).is_multiple_of(size[axis]) {
+            f[axis] = target[axis].wrapping_sub(half[axis] as u32);
+            s[axis] = target[axis].wrapping_add(half[axis] as u32);
+        }
+    }
+    (f, s)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Derivation<const N: usize> {
+    pub level: usize,
+    pub noise: f64,
+    pub parents: [[u32; N]; 2],
+    pub parent_values: [f64; 2],
+    /// The height displaced from, the average of the parents unless terraced.
+    pub base: f64,
+    /// The displacement after its [`DisplacementTransform`] and [`Modulation`].
+    pub displacement: f64,
+    /// The factor the displacement was scaled by, see [`Modulation`].
+    pub modulation: f64,
+    /// The shift towards any pinned heights, see [`FractalNoise::pin`].
+    pub conditioning: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointProvenance<const N: usize> {
+    pub point: [u32; N],
+    pub value: f64,
+    /// `None` for the root, whose value is not derived from anything.
+    pub derivation: Option<Derivation<N>>,
+}
+
+trait ValidPointsArray<T, const N: usize>: IndexMut<usize, Output = T> + AsRef<[T]> {
+    fn init(source: impl Iterator<Item = T>) -> Self;
+}
+
+impl<T, const N: usize> ValidPointsArray<T, N> for Vec<T> {
+    fn init(source: impl Iterator<Item = T>) -> Self {
+        source.collect()
+    }
+}
+
+/// Stack storage for up to `CAP` points, which holds every corner of a cell when `CAP == 1 << N`.
+struct InlinePoints<T, const CAP: usize> {
+    points: [T; CAP],
+    len: usize,
+}
+
+impl<T: Copy, const N: usize, const CAP: usize> ValidPointsArray<T, N> for InlinePoints<T, CAP> {
+    fn init(mut source: impl Iterator<Item = T>) -> Self {
+        let next = source.next().unwrap();
+        let mut points = [next; CAP];
+        let mut len = 1;
+        for (r, s) in points[1..].iter_mut().zip(source.by_ref()) {
+            *r = s;
+            len += 1;
+        }
+        debug_assert!(source.next().is_none(), "more than {CAP} points");
+        Self { points, len }
+    }
+}
+
+impl<T, const CAP: usize> AsRef<[T]> for InlinePoints<T, CAP> {
+    fn as_ref(&self) -> &[T] {
+        &self.points[..self.len]
+    }
+}
+
+impl<T, const CAP: usize> Index<usize> for InlinePoints<T, CAP> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.as_ref()[index]
+    }
+}
+
+impl<T, const CAP: usize> IndexMut<usize> for InlinePoints<T, CAP> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        &mut self.points[..self.len][index]
+    }
+}
+
+/// Where a [`Ray`] meets a surface, and how it got there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub point: Point3<f64>,
+    /// The distance along the ray, which is normalised.
+    pub t: f64,
+    /// The unit normal of the [`Patch`] over the cell that was hit.
+    pub normal: Vector3<f64>,
+    pub base: [u32; 2],
+    pub size: u64,
+    pub level: usize,
+    /// How many cells the march visited.
+    pub steps: usize,
+    /// Whether the march went down to a single lattice step, rather than stopping at a coarser cell
+    /// once its remaining noise fell below the tolerance.
+    pub exact: bool,
+}
+
+/// The surface a [`Ray`] meets inside a terminated cell, spanned by the heights at its `(0, 0)`,
+/// `(1, 0)`, `(0, 1)` and `(1, 1)` corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Patch {
+    /// Bilinear interpolation between the corners, as [`Raster`](field::Raster) and
+    /// [`WorldField`](world::WorldField) sample between lattice points.
+    #[default]
+    Bilinear,
+    /// Two planar triangles split along the diagonal from the base, the Kuhn triangulation of
+    /// [`Subdivision::Simplex`].
+    Triangles,
+}
+
+impl Patch {
+    /// The height at `fractions` of the way across the cell.
+    pub fn height(self, corners: [f64; 4], fractions: [f64; 2]) -> f64 {
+        let [h00, h10, h01, h11] = corners;
+        let [u, v] = fractions;
+        match self {
+            Patch::Bilinear => {
+                (h00 * (1.0 - u) + h10 * u) * (1.0 - v) + (h01 * (1.0 - u) + h11 * u) * v
+            }
+            Patch::Triangles => {
+                let [du, dv] = self.slope(corners, fractions);
+                h00 + du * u + dv * v
+            }
+        }
+    }
+
+    /// The upward unit normal at `fractions` of the way across a cell `size` wide.
+    pub fn normal(self, corners: [f64; 4], fractions: [f64; 2], size: f64) -> Vector3<f64> {
+        let [du, dv] = self.slope(corners, fractions);
+        Vector3::new(-du / size, 1.0, -dv / size).normalize()
+    }
+
+    /// The change in height across the whole cell along each axis at `fractions`.
+    fn slope(self, corners: [f64; 4], [u, v]: [f64; 2]) -> [f64; 2] {
+        let [h00, h10, h01, h11] = corners;
+        match self {
+            Patch::Bilinear => [
+                (h10 - h00) * (1.0 - v) + (h11 - h01) * v,
+                (h01 - h00) * (1.0 - u) + (h11 - h10) * u,
+            ],
+            Patch::Triangles if u >= v => [h10 - h00, h11 - h10],
+            Patch::Triangles => [h11 - h01, h01 - h00],
+        }
+    }
+
+    /// The smallest `s` in `0..=span` at which `start + direction * s`, in fractions of the cell
+    /// across and heights up, crosses the patch.
+    fn crossing(
+        self,
+        corners: [f64; 4],
+        start: [f64; 3],
+        direction: [f64; 3],
+        span: f64,
+    ) -> Option<f64> {
+        const SLACK: f64 = 1e-9;
+        let [h00, h10, h01, h11] = corners;
+        let ([u0, v0, y0], [du, dv, dy]) = (start, direction);
+        let inside = |s: f64, triangle: Option<bool>| {
+            let (u, v) = (u0 + du * s, v0 + dv * s);
+            (-SLACK..=span + SLACK).contains(&s)
+                && (-SLACK..=1.0 + SLACK).contains(&u)
+                && (-SLACK..=1.0 + SLACK).contains(&v)
+                && triangle.is_none_or(|lower| (u - v >= -SLACK) == lower || (u - v).abs() <= SLACK)
+        };
+        let first = |roots: &mut dyn Iterator<Item = (f64, Option<bool>)>| {
+            roots
+                .filter(|&(s, triangle)| s.is_finite() && inside(s, triangle))
+                .map(|(s, _)| s.clamp(0.0, span))
+                .min_by(f64::total_cmp)
+        };
+        match self {
+            Patch::Bilinear => {
+                // y0 + dy s - h(u0 + du s, v0 + dv s) = a s^2 + b s + c
+                let (b_u, b_v, b_uv) = (h10 - h00, h01 - h00, h00 - h10 - h01 + h11);
+                let a = -b_uv * du * dv;
+                let b = dy - b_u * du - b_v * dv - b_uv * (u0 * dv + v0 * du);
+                let c = y0 - h00 - b_u * u0 - b_v * v0 - b_uv * u0 * v0;
+                let roots = match a == 0.0 || (a * span * span).abs() <= (b * span).abs() * 1e-12 {
+                    true => [-c / b, f64::NAN],
+                    false => {
+                        let discriminant = b * b - 4.0 * a * c;
+                        if discriminant < 0.0 {
+                            return None;
+                        }
+                        let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+                        [q / a, c / q]
+                    }
+                };
+                first(&mut roots.into_iter().map(|s| (s, None)))
+            }
+            Patch::Triangles => {
+                let planes = [
+                    (true, [h10 - h00, h11 - h10]),
+                    (false, [h11 - h01, h01 - h00]),
+                ];
+                first(&mut planes.into_iter().map(|(lower, [su, sv])| {
+                    let c = y0 - h00 - su * u0 - sv * v0;
+                    let b = dy - su * du - sv * dv;
+                    (-c / b, Some(lower))
+                }))
+            }
+        }
+    }
+}
+
+/// The range of `t` for which `origin + direction * t` lies within `range`.
+fn slab(origin: f64, direction: f64, range: &RangeInclusive<f64>) -> Option<(f64, f64)> {
+    if direction == 0.0 {
+        return range
+            .contains(&origin)
+            .then_some((f64::NEG_INFINITY, f64::INFINITY));
+    }
+    let t0 = (range.start() - origin) / direction;
+    let t1 = (range.end() - origin) / direction;
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+#[derive(Copy, Clone)]
+pub struct Ray {
+    direction: Vector3<f64>,
+    origin: Point3<f64>,
+    patch: Patch,
+}
+
+impl Ray {
     pub fn new(direction: Vector3<f64>, origin: Point3<f64>) -> Self {
-        let normalised = direction.normalize();
         Self {
-            direction: normalised,
-            inv_dir: normalised.map(|f| 1.0 / f),
+            direction: direction.normalize(),
             origin,
+            patch: Patch::default(),
         }
     }
 
-    fn intersection_candidates<'a, T: Iterator<Item = (usize, [u32; 2])>>(
+    pub fn origin(&self) -> Point3<f64> {
+        self.origin
+    }
+
+    /// The direction of the ray, normalised.
+    pub fn direction(&self) -> Vector3<f64> {
+        self.direction
+    }
+
+    pub fn with_patch(mut self, patch: Patch) -> Self {
+        self.patch = patch;
+        self
+    }
+
+    pub fn patch(&self) -> Patch {
+        self.patch
+    }
+
+    /// The first `t` within `range` at which the ray crosses the patch over the cell `size` wide
+    /// at `base` with `corners`.
+    fn cross_patch(
         &self,
-        noise: &'a mut FractalNoise<2>,
-        nextpoint: u32,
-        options: T,
-    ) -> impl Iterator<Item = (usize, RectangularPrism, f64)> + use<'_, 'a, T> {
-        options
-            .map(move |(i, c)| (i, RectangularPrism::around(c, noise, nextpoint)))
-            .filter_map(move |(i, prism)| {
-                let mut unbounded = prism;
-                unbounded.lower.y = f64::MIN;
-                unbounded.upper.y = f64::MAX;
-                unbounded.intersect(&self).map(|(t, _)| (i, prism, t))
-            })
+        base: [f64; 2],
+        size: f64,
+        corners: [f64; 4],
+        range: Range<f64>,
+    ) -> Option<f64> {
+        let [x, z] = base.map(|n| n..=n + size);
+        let (x0, x1) = slab(self.origin.x, self.direction.x, &x)?;
+        let (z0, z1) = slab(self.origin.z, self.direction.z, &z)?;
+        let (enter, exit) = (range.start.max(x0).max(z0), range.end.min(x1).min(z1));
+        if enter > exit {
+            return None;
+        }
+        // measure from the entry into the cell, where the fractions are small
+        let at = self.origin + self.direction * enter;
+        let start = [(at.x - x.start()) / size, (at.z - z.start()) / size, at.y];
+        let direction = [
+            self.direction.x / size,
+            self.direction.z / size,
+            self.direction.y,
+        ];
+        self.patch
+            .crossing(corners, start, direction, exit - enter)
+            .map(|s| enter + s)
     }
 
-    pub fn intersect(&self, noise: &mut FractalNoise<2>, max: f64) -> Option<Point3<f64>> {
-        let global_bounds = RectangularPrism::new(
-            [
-                Point3::new(0.0, 0.0, 0.0),
-                Point3::new(
-                    u32::MAX as f64 + 1.0,
-                    noise.upper_bound(0),
-                    u32::MAX as f64 + 1.0,
-                ),
-            ]
-            .into_iter(),
-        );
-        let Some((entry, _)) = global_bounds.intersect(&self) else {
+    /// The first hit of the ray on the surface of `noise` with its parameter within `range`, over
+    /// the lattice from `0` to `1 << 32` along `x` and `z`.
+    ///
+    /// The ray marches front to back through the cells [`NoiseField::cached_bounds_for`] finds
+    /// under it, skipping each whose heights it passes above or below and looking closer where it
+    /// reaches them, until it crosses the [`Patch`] of a terminated one. The ray may point
+    /// anywhere and start above or below the surface, which it then hits on its way out.
+    pub fn intersect<F: NoiseField<2> + ?Sized>(
+        &self,
+        noise: &mut F,
+        range: Range<f64>,
+    ) -> Option<RayHit> {
+        let lattice = 0.0..=(1u64 << 32) as f64;
+        let (x0, x1) = slab(self.origin.x, self.direction.x, &lattice)?;
+        let (z0, z1) = slab(self.origin.z, self.direction.z, &lattice)?;
+        let (tmin, tmax) = (range.start.max(x0).max(z0), range.end.min(x1).min(z1));
+        if tmin > tmax {
             return None;
+        }
+        self.descend(noise, tmin..tmax)
+    }
+
+    /// Whether any part of the ray with its parameter within `range` lies below the surface of
+    /// `noise`, as for a shadow ray. It marches like [`Ray::intersect`], but stops at the first
+    /// cell the ray passes beneath rather than finding where it crosses, and a ray starting below
+    /// the surface is occluded as soon as [`NoiseField::cached_bounds_for`] finds a cell above its
+    /// start.
+    pub fn occluded<F: NoiseField<2> + ?Sized>(&self, noise: &mut F, range: Range<f64>) -> bool {
+        let lattice = 0.0..=(1u64 << 32) as f64;
+        let Some((x0, x1)) = slab(self.origin.x, self.direction.x, &lattice) else {
+            return false;
         };
+        let Some((z0, z1)) = slab(self.origin.z, self.direction.z, &lattice) else {
+            return false;
+        };
+        let (tmin, tmax) = (range.start.max(x0).max(z0), range.end.min(x1).min(z1));
+
+        let (mut t, mut level) = (tmin, 0);
+        while t <= tmax {
+            let at = self.origin + self.direction * t;
+            let bounds = noise.cached_bounds_for(self.lattice_point(at), at.y, level);
+            let (base, size) = (bounds.base(), bounds.size());
+            let [x, z] = base.map(|n| n as f64..=n as f64 + size as f64);
+            let (Some((_, x1)), Some((_, z1))) = (
+                slab(self.origin.x, self.direction.x, &x),
+                slab(self.origin.z, self.direction.z, &z),
+            ) else {
+                return false;
+            };
+            let exit = tmax.min(x1).min(z1);
 
-        let mut direction = [None, None];
-        let mut intersection = if entry < 0.0 { 0.0 } else { entry };
-        let mut last_iterations = 0;
-        while intersection < max {
-            let marched = self.origin + self.direction * intersection;
-            let query = [marched.x as u32, marched.z as u32];
-            let (terminated, range, base, iterations, nextpoint) =
-                noise.cached_bounds_for(query, marched.y, last_iterations);
-            if terminated {
-                if let Some((intersection, _)) =
-                    RectangularPrism::around(base, noise, nextpoint).intersect(self)
+            if bounds.terminated() || bounds.level() >= 32 {
+                let corners = [0, 1, 2, 3].map(|c| noise.cell_corner(base, bounds.level(), c));
+                let fractions = [(at.x, base[0]), (at.z, base[1])]
+                    .map(|(n, b)| ((n - b as f64) / size as f64).clamp(0.0, 1.0));
+                if at.y < self.patch.height(corners, fractions)
+                    || self
+                        .cross_patch(base.map(|n| n as f64), size as f64, corners, t..exit)
+                        .is_some()
                 {
-                    // println!("found intersection at: {actual}!");
-                    return Some(self.origin + self.direction * intersection);
+                    return true;
+                }
+            } else {
+                let heights = bounds.heights();
+                let [y0, y1] = [t, exit].map(|t| self.origin.y + self.direction.y * t);
+                // some of the ray lies beneath every height in the cell
+                if y0.min(y1) < *heights.start() {
+                    return true;
+                }
+                if y0.min(y1) <= *heights.end() {
+                    // the ray dips into the heights of the cell inside it, so look closer there
+                    let (enter, _) =
+                        slab(self.origin.y, self.direction.y, &heights).unwrap_or((t, exit));
+                    level = bounds.level() + (enter <= t) as usize;
+                    t = enter.max(t);
+                    continue;
                 }
             }
+            level = bounds.level().saturating_sub(1);
+            t = if exit > t { exit } else { t.next_up() };
+        }
+        false
+    }
 
-            let mut prism = RectangularPrism::around(base, noise, nextpoint);
-            prism.lower.y = *range.start() + EPSILON;
-            prism.upper.y = *range.end() - EPSILON;
-            let base_intersection = prism
-                .intersect(&self)
-                .map(|(t, _)| t)
-                .filter(|t| *t > intersection && t.is_normal())
-                .map(|t| (t, iterations)); // we know that we can directly step the bounds
-            let Some((actual, iterations)) = ({
-                let options = [
-                    [base[0].overflowing_add(nextpoint).0, base[1]],
-                    [base[0], base[1].overflowing_add(nextpoint).0],
-                    [base[0].overflowing_sub(nextpoint).0, base[1]],
-                    [base[0], base[1].overflowing_sub(nextpoint).0],
-                ];
+    fn descend<F: NoiseField<2> + ?Sized>(
+        &self,
+        noise: &mut F,
+        range: Range<f64>,
+    ) -> Option<RayHit> {
+        let (mut t, mut level, mut steps) = (range.start, 0, 0);
+        while t <= range.end {
+            steps += 1;
+            let at = self.origin + self.direction * t;
+            let bounds = noise.cached_bounds_for(self.lattice_point(at), at.y, level);
+            let (base, size) = (bounds.base(), bounds.size());
+            let [x, z] = base.map(|n| n as f64..=n as f64 + size as f64);
+            let (_, x1) = slab(self.origin.x, self.direction.x, &x)?;
+            let (_, z1) = slab(self.origin.z, self.direction.z, &z)?;
+            let exit = range.end.min(x1).min(z1);
 
-                match direction {
-                    [None, None] => self
-                        .intersection_candidates(noise, nextpoint, options.into_iter().enumerate())
-                        .filter(|(_, _, t)| *t > intersection && t.is_normal())
-                        .inspect(|(i, _, _)| {
-                            if let Some(direction) = direction.iter_mut().find(|o| o.is_none()) {
-                                *direction = Some(*i)
-                            }
-                        })
-                        .map(|(_, _, t)| (t, iterations.saturating_sub(1)))
-                        .chain(base_intersection)
-                        .min_by(|(t1, _), (t2, _)| t1.total_cmp(t2)),
-                    [Some(first), None] => self
-                        .intersection_candidates(
-                            noise,
-                            nextpoint,
-                            [
-                                first.overflowing_sub(1).0 % 4,
-                                first,
-                                first.overflowing_add(1).0 % 4,
-                            ]
-                            .into_iter()
-                            .map(|i| (i, options[i])),
-                        )
-                        .filter(|(_, _, t)| *t > intersection && t.is_normal())
-                        .inspect(|(i, _, _)| {
-                            if *i != first {
-                                if let Some(direction) = direction.iter_mut().find(|o| o.is_none())
-                                {
-                                    *direction = Some(*i)
-                                }
-                            }
-                        })
-                        .map(|(_, _, t)| (t, iterations.saturating_sub(1)))
-                        .chain(base_intersection)
-                        .min_by(|(t1, _), (t2, _)| t1.total_cmp(t2)),
-                    [Some(first), Some(second)] => self
-                        .intersection_candidates(
-                            noise,
-                            nextpoint,
-                            [first, second].into_iter().map(|i| (i, options[i])),
-                        )
-                        .map(|(_, _, t)| t)
-                        .filter(|t| *t > intersection && t.is_normal())
-                        .map(|t| (t, iterations.saturating_sub(1)))
-                        .chain(base_intersection)
-                        .min_by(|(t1, _), (t2, _)| t1.total_cmp(t2)),
-                    _ => unreachable!("This is not possible by construction."),
+            if bounds.terminated() || bounds.level() >= 32 {
+                // nothing below strays from the corners, so the patch between them is the surface
+                let corners = [0, 1, 2, 3].map(|c| noise.cell_corner(base, bounds.level(), c));
+                let x = base.map(|n| n as f64);
+                if let Some(t) = self.cross_patch(x, size as f64, corners, t..exit) {
+                    let point = self.origin + self.direction * t;
+                    let fractions = [(point.x, base[0]), (point.z, base[1])]
+                        .map(|(n, b)| ((n - b as f64) / size as f64).clamp(0.0, 1.0));
+                    return Some(RayHit {
+                        point,
+                        t,
+                        normal: self.patch.normal(corners, fractions, size as f64),
+                        base,
+                        size,
+                        level: bounds.level(),
+                        steps,
+                        exact: bounds.level() >= 32,
+                    });
                 }
-            }) else {
-                return None;
-            };
+            } else if let Some((y0, y1)) = slab(self.origin.y, self.direction.y, &bounds.heights())
+            {
+                let (enter, leave) = (t.max(y0), exit.min(y1));
+                if enter <= leave {
+                    // the ray reaches the heights of the cell inside it, so look closer from there,
+                    // going a level deeper if rounding left it where it was
+                    level = bounds.level() + (enter <= t) as usize;
+                    t = enter;
+                    continue;
+                }
+            }
+            // on to the next cell along the ray, starting from the parent of this one
+            level = bounds.level().saturating_sub(1);
+            t = if exit > t { exit } else { t.next_up() };
+        }
+        None
+    }
 
-            intersection = actual;
-            last_iterations = iterations;
+    /// The lattice point whose cell `at` lies in, taking the cell ahead along the ray when `at`
+    /// is on an edge.
+    fn lattice_point(&self, at: Point3<f64>) -> [u32; 2] {
+        [(at.x, self.direction.x), (at.z, self.direction.z)].map(|(n, d)| {
+            let cell = if d < 0.0 { n.ceil() - 1.0 } else { n.floor() };
+            cell.clamp(0.0, u32::MAX as f64) as u32
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sample_displacement;
+    use crate::test_support::{assert_bounds_hold, scattered};
+    use crate::{
+        BoundKind, DisplacementTransform, FractalNoise, Modulation, Patch, PointOrder, Ray,
+        Subdivision,
+    };
+    use cgmath::{InnerSpace, Point3, Vector3};
+    use std::collections::HashMap;
+
+    fn eager<const N: usize>(noise: f64, decay: f64, seed: i64, levels: usize) -> FractalNoise<N> {
+        let mut field = FractalNoise::<N>::new(noise, decay, seed);
+        for _ in 0..levels {
+            if !field.step_midpoints().unwrap() {
+                break;
+            }
+        }
+        field
+    }
+
+    fn assert_lazy_matches_eager<const N: usize>(levels: usize) {
+        for (noise, decay, seed) in FIELDS {
+            let eager = eager::<N>(noise, decay, seed, levels);
+            let mut lazy = FractalNoise::<N>::new(noise, decay, seed);
+            for (&point, &value) in eager.values() {
+                assert_eq!(
+                    lazy.find_point(point).to_bits(),
+                    value.to_bits(),
+                    "{point:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lazy_matches_eager() {
+        assert_lazy_matches_eager::<1>(10);
+        assert_lazy_matches_eager::<2>(6);
+        assert_lazy_matches_eager::<3>(4);
+    }
+
+    fn assert_order_independent<const N: usize>() {
+        for (noise, decay, seed) in FIELDS {
+            let points = scattered::<N>(seed as u64, 200);
+
+            let mut forward = FractalNoise::<N>::new(noise, decay, seed);
+            let expected = points
+                .iter()
+                .map(|&p| forward.find_point(p).to_bits())
+                .collect::<Vec<_>>();
+
+            let mut backward = FractalNoise::<N>::new(noise, decay, seed);
+            let mut reversed = points
+                .iter()
+                .rev()
+                .map(|&p| backward.find_point(p).to_bits())
+                .collect::<Vec<_>>();
+            reversed.reverse();
+            assert_eq!(expected, reversed);
+
+            let mut warmed = eager::<N>(noise, decay, seed, 4);
+            for &point in &scattered::<N>(!(seed as u64), 50) {
+                warmed.find_point(point);
+            }
+            let found = points
+                .iter()
+                .map(|&p| warmed.find_point(p).to_bits())
+                .collect::<Vec<_>>();
+            assert_eq!(expected, found);
+        }
+    }
+
+    #[test]
+    fn independent_of_query_order() {
+        assert_order_independent::<1>();
+        assert_order_independent::<2>();
+        assert_order_independent::<3>();
+    }
+
+    #[test]
+    fn eager_after_lazy_matches_eager() {
+        for (noise, decay, seed) in FIELDS {
+            let expected = eager::<2>(noise, decay, seed, 5);
+
+            let mut mixed = FractalNoise::<2>::new(noise, decay, seed);
+            let points = scattered::<2>(seed as u64, 50);
+            let lazy = points
+                .iter()
+                .map(|&p| (p, mixed.find_point(p)))
+                .collect::<HashMap<_, _>>();
+            while mixed.iterations() < expected.iterations() {
+                mixed.step_midpoints().unwrap();
+            }
+            for (point, value) in expected.values() {
+                assert_eq!(mixed.values()[point].to_bits(), value.to_bits());
+            }
+            for (point, value) in lazy {
+                assert_eq!(mixed.values()[&point].to_bits(), value.to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn find_points_matches_find_point() {
+        for (noise, decay, seed) in FIELDS {
+            let mut points = scattered::<2>(seed as u64, 100);
+            points.extend((0..100).map(|i| [0x4000_0000 + i % 10 * 0x1000, 0x8000_0000 + i / 10]));
+            points.push(points[3]);
+            for subdivision in [Subdivision::Cube, Subdivision::Simplex] {
+                let mut single = FractalNoise::<2>::new(noise, decay, seed);
+                let mut batched = single.clone().with_subdivision(subdivision);
+                let expected = points
+                    .iter()
+                    .map(|&p| single.find_point(p).to_bits())
+                    .collect::<Vec<_>>();
+                let found = batched.find_points(&points);
+                assert_eq!(
+                    expected,
+                    found.iter().map(|v| v.to_bits()).collect::<Vec<_>>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_values_are_deterministic() {
+        let mut field = eager::<2>(1.0, 0.5, 0, 2);
+        let m = 1 << 30;
+        let morton = field
+            .ordered_values(PointOrder::Morton, Some(1), Some(&[0..=m, 0..=m]))
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>();
+        assert_eq!(morton, [[0, m], [m, 0], [m, m]]);
+
+        field.find_point([3, 5]);
+        let row_major = field
+            .ordered_values(PointOrder::RowMajor, None, None)
+            .map(|(p, _)| p)
+            .collect::<Vec<_>>();
+        assert!(row_major.is_sorted());
+        assert_eq!(row_major.len(), field.values().len());
+        // only the points first created at a level, not the coarser ones it shares
+        let first = field.ordered_values(PointOrder::Morton, Some(0), None);
+        assert_eq!(first.len(), 3);
+        let second = field
+            .ordered_values(PointOrder::Morton, Some(1), None)
+            .collect::<Vec<_>>();
+        assert_eq!(second.len(), 12);
+        assert!(second.iter().all(|(p, _)| field.explain_point(*p)[0]
+            .derivation
+            .is_some_and(|d| d.level == 1)));
+    }
+
+    #[test]
+    fn values_within_upper_bound() {
+        for (noise, decay, seed) in FIELDS {
+            let mut field = eager::<2>(noise, decay, seed, 6);
+            for &point in &scattered::<2>(seed as u64, 200) {
+                field.find_point(point);
+            }
+            let max = field.upper_bound(0);
+            for (point, &value) in field.values() {
+                assert!((0.0..=max).contains(&value), "{point:?} = {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn pinned_points_hold() {
+        for (noise, decay, seed) in FIELDS {
+            let pins = scattered::<2>(seed as u64, 8);
+            let mut lazy = FractalNoise::<2>::new(noise, decay, seed);
+            for (i, &point) in pins.iter().enumerate() {
+                lazy.pin(point, noise * i as f64 / 4.0).unwrap();
+            }
+            assert!(lazy.clone().pin([0, 0], 0.0).is_ok());
+            for (i, &point) in pins.iter().enumerate() {
+                let value = lazy.find_point(point);
+                assert!(
+                    (value - noise * i as f64 / 4.0).abs() < 1e-6 * noise,
+                    "{value}"
+                );
+            }
+            let points = cached(&lazy);
+            assert_bounds_hold(&mut lazy, &points, 0);
+            assert!(lazy.pin([1, 1], 0.0).is_err());
+        }
+
+        for kind in [BoundKind::Geometric, BoundKind::Dimensional] {
+            for (noise, decay, seed) in FIELDS {
+                let mut field = FractalNoise::<2>::new(noise, decay, seed).with_bound_kind(kind);
+                let pins = scattered::<2>(seed as u64, 6)
+                    .into_iter()
+                    .map(|p| p.map(|c| c & !(u32::MAX >> 6)))
+                    .collect::<Vec<_>>();
+                for (i, &point) in pins.iter().enumerate() {
+                    field.pin(point, noise * (i % 3) as f64).unwrap();
+                }
+                for _ in 0..6 {
+                    field.step_midpoints().unwrap();
+                }
+                for (i, point) in pins.iter().enumerate() {
+                    let value = field.values()[point];
+                    assert!(
+                        (value - noise * (i % 3) as f64).abs() < 1e-6 * noise,
+                        "{value}"
+                    );
+                }
+                let points = cached(&field);
+                assert_bounds_hold(&mut field, &points, 6);
+            }
+        }
+    }
+
+    #[test]
+    fn dependent_pins_are_refused() {
+        // without noise below the first level, both quarters are the average of the same parents
+        let mut field = FractalNoise::<1>::new(1.0, 0.0, 3);
+        field.pin([1 << 30], 0.25).unwrap();
+        assert!(field.pin([3 << 30], 0.75).is_err());
+        assert_eq!(field.pins(), [([1 << 30], 0.25)]);
+        assert!((field.find_point([1 << 30]) - 0.25).abs() < 1e-9);
+        assert!((field.find_point([3 << 30]) - 0.25).abs() < 1e-9);
+
+        assert!(FractalNoise::<2>::new(0.0, 0.5, 3)
+            .pin([5, 7], 1.0)
+            .is_err());
+    }
+
+    #[test]
+    fn coarse_grid_is_kept_and_bounded() {
+        for kind in [BoundKind::Geometric, BoundKind::Dimensional] {
+            for (noise, decay, seed) in FIELDS {
+                let grid = scattered::<1>(seed as u64, 64)
+                    .into_iter()
+                    .map(|[h]| h as f64 / u32::MAX as f64 * noise * 4.0)
+                    .collect::<Vec<_>>();
+                let mut field = FractalNoise::<2>::from_coarse_grid(&grid, 3, noise, decay, seed)
+                    .unwrap()
+                    .with_bound_kind(kind);
+                assert_eq!(field.find_point([1 << 28, 2 << 28]), grid[10]);
+                assert_eq!(field.find_point([14 << 28, 13 << 28]), grid[10]);
+                for _ in 0..4 {
+                    field.step_midpoints().unwrap();
+                }
+                for point in scattered::<2>(!(seed as u64), 50) {
+                    field.find_point(point);
+                }
+                let points = cached(&field);
+                assert_bounds_hold(&mut field, &points, 8);
+            }
+        }
+        assert!(FractalNoise::<2>::from_coarse_grid(&[0.0; 15], 2, 1.0, 0.5, 0).is_err());
+    }
+
+    #[test]
+    fn coarse_grid_edges_keep_to_their_samples() {
+        // heights rising to the east, so wrapping to the west edge would pull them down
+        let grid = (0..64)
+            .map(|i| (i % 8 * 10 + i / 8) as f64)
+            .collect::<Vec<_>>();
+        let mut field = FractalNoise::<2>::from_coarse_grid(&grid, 3, 0.0, 0.5, 0).unwrap();
+        for row in 0..7 {
+            let samples = [grid[row * 8 + 7], grid[row * 8 + 15]];
+            let (lowest, highest) = (samples[0].min(samples[1]), samples[0].max(samples[1]));
+            // the cell east of the last column of samples
+            for [x, z] in scattered::<2>(row as u64, 20) {
+                let point = [(row << 28) as u32 + (x >> 4), (7 << 28) + (z >> 4)];
+                let value = field.find_point(point);
+                assert!((lowest..=highest).contains(&value), "{point:?} = {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn modulated_bounds_hold() {
+        for (noise, decay, seed) in FIELDS {
+            let plains = Modulation::new(
+                2.0,
+                |[x, _], average| {
+                    if x < 1 << 31 {
+                        0.0
+                    } else {
+                        3.0 + average
+                    }
+                },
+            )
+            .unwrap();
+            let rough = Modulation::from_field(FractalNoise::new(1.0, 0.5, seed + 1), 0.5).unwrap();
+            for modulation in [plains, rough] {
+                let mut field =
+                    FractalNoise::<2>::new(noise, decay, seed).with_modulation(modulation);
+                for _ in 0..5 {
+                    field.step_midpoints().unwrap();
+                }
+                for point in scattered::<2>(seed as u64, 50) {
+                    field.find_point(point);
+                }
+                let points = cached(&field);
+                assert_bounds_hold(&mut field, &points, 6);
+            }
+        }
+
+        let mut field = FractalNoise::<1>::new(1.0, 0.5, 3)
+            .with_modulation(Modulation::new(0.0, |_, _| 1.0).unwrap());
+        let [first, last] = [field.find_point([0]), field.find_point([u32::MAX])];
+        assert_eq!(first, last);
+
+        for max in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert!(Modulation::<1>::new(max, |_, _| 1.0).is_err());
+        }
+        assert!(
+            Modulation::from_field(FractalNoise::<1>::new(1.0, 0.5, 3), f64::INFINITY).is_err()
+        );
+    }
+
+    #[test]
+    fn modulation_scales_termination() {
+        let strong = Modulation::new(1e6, |_, _| 1e6).unwrap();
+        let mut field = FractalNoise::<2>::new(1.0, 0.5, 3).with_modulation(strong);
+        let point = [0x1234_5678, 0x9abc_def0];
+        // the noise alone drops below the tolerance at level 17, but the displacements do not
+        assert!((0..32).all(|level| !field.cell_bounds(point, level).terminated()));
+
+        let weak = Modulation::new(1e-3, |_, _| 1e-3).unwrap();
+        let mut field = FractalNoise::<2>::new(1.0, 0.5, 3).with_modulation(weak);
+        assert!(!field.cell_bounds(point, 6).terminated());
+        assert!(field.cell_bounds(point, 7).terminated());
+
+        let rough = Modulation::from_field(FractalNoise::new(1.0, 0.5, 4), 0.5).unwrap();
+        let copy = rough.clone();
+        assert_eq!(copy.factor(point, 0.0), rough.factor(point, 0.0));
+    }
+
+    #[test]
+    fn transformed_bounds_hold() {
+        let transforms = [
+            DisplacementTransform::Ridged,
+            DisplacementTransform::Billowy,
+            DisplacementTransform::Squared,
+            DisplacementTransform::Terrace { step: 0.3 },
+        ];
+        for kind in [BoundKind::Geometric, BoundKind::Dimensional] {
+            for (i, transform) in transforms.into_iter().enumerate() {
+                for (noise, decay, seed) in FIELDS {
+                    let mut field = FractalNoise::<2>::new(noise, decay, seed)
+                        .with_bound_kind(kind)
+                        .with_transform(transform);
+                    for _ in 0..5 {
+                        field.step_midpoints().unwrap();
+                    }
+                    for point in scattered::<2>(seed as u64, 50) {
+                        let value = field.find_point(point);
+                        let explained = field.explain_point(point)[0];
+                        let derivation = explained.derivation.unwrap();
+                        assert_eq!(derivation.base + derivation.displacement, value);
+                    }
+
+                    // a single transformed level among untransformed ones
+                    let level = i + 1;
+                    let mut field = FractalNoise::<2>::new(noise, decay, seed)
+                        .with_bound_kind(kind)
+                        .with_level_transform(level, transform)
+                        .unwrap();
+                    for _ in 0..5 {
+                        field.step_midpoints().unwrap();
+                    }
+                    let mut transformed = 0;
+                    for point in scattered::<2>(seed as u64, 20) {
+                        field.find_point(point);
+                        for provenance in field.explain_point(point) {
+                            let Some(derivation) = provenance.derivation else {
+                                continue;
+                            };
+                            let expected = field.transform(derivation.level);
+                            assert_eq!(derivation.level == level, expected == transform);
+                            transformed += (derivation.level == level) as usize;
+
+                            let [f, s] = derivation.parents;
+                            let [f_val, s_val] = derivation.parent_values;
+                            let sample = sample_displacement(f, s, derivation.noise, seed);
+                            assert_eq!(derivation.base, expected.base((f_val + s_val) * 0.5));
+                            assert_eq!(
+                                derivation.displacement,
+                                expected.displace(sample, derivation.noise)
+                            );
+                        }
+                    }
+                    assert!(transformed > 0);
+                    let points = cached(&field);
+                    assert_bounds_hold(&mut field, &points, 6);
+                }
+            }
+        }
+        assert!(FractalNoise::<2>::new(1.0, 0.5, 0)
+            .with_level_transform(32, DisplacementTransform::Ridged)
+            .is_err());
+    }
+
+    #[test]
+    fn cached_bounds_contain_heights_below() {
+        for (noise, decay, seed) in FIELDS {
+            let mut field = FractalNoise::<2>::new(noise, decay, seed);
+            let max = field.upper_bound(0);
+            let queries = scattered::<2>(seed as u64, 100);
+            let offsets = scattered::<2>(!(seed as u64), 20);
+            for (i, &query) in queries.iter().enumerate() {
+                let height = max * i as f64 / queries.len() as f64;
+                let bounds = field.cached_bounds_for(query, height, 0);
+                let size = bounds.size();
+                for offset in &offsets {
+                    let mut below = bounds.base();
+                    below
+                        .iter_mut()
+                        .zip(offset)
+                        .for_each(|(b, o)| *b = b.wrapping_add((*o as u64 % size) as u32));
+                    let value = field.find_point(below);
+                    assert!(
+                        bounds.contains(value),
+                        "{below:?} = {value} escapes {bounds:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn provenance_matches_find_point() {
+        for (noise, decay, seed) in FIELDS {
+            let mut field = FractalNoise::<2>::new(noise, decay, seed);
+            for point in scattered::<2>(seed as u64, 20) {
+                let value = field.find_point(point);
+                let chain = field.explain_point(point);
+                assert_eq!(chain[0].point, point);
+                assert_eq!(chain[0].value.to_bits(), value.to_bits());
+                assert!(chain.last().unwrap().derivation.is_none());
+
+                let explained = chain
+                    .iter()
+                    .map(|p| (p.point, *p))
+                    .collect::<HashMap<_, _>>();
+                assert_eq!(explained.len(), chain.len());
+                for provenance in &chain {
+                    let found = field.find_point(provenance.point);
+                    assert_eq!(provenance.value.to_bits(), found.to_bits());
+                    let Some(derivation) = provenance.derivation else {
+                        assert_eq!(provenance.point, [0, 0]);
+                        continue;
+                    };
+                    assert_eq!(derivation.base + derivation.displacement, provenance.value);
+                    assert_eq!(derivation.noise, field.noise(derivation.level));
+                    for (parent, parent_value) in
+                        derivation.parents.into_iter().zip(derivation.parent_values)
+                    {
+                        let parent = explained[&parent];
+                        assert_eq!(parent.value.to_bits(), parent_value.to_bits());
+                        assert!(parent.derivation.map_or(0, |d| d.level + 1) <= derivation.level);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_hierarchy_nests() {
+        for kind in [BoundKind::Geometric, BoundKind::Dimensional] {
+            for (noise, decay, seed) in FIELDS {
+                let mut field = FractalNoise::<2>::new(noise, decay, seed).with_bound_kind(kind);
+                for point in scattered::<2>(seed as u64, 10) {
+                    let value = field.find_point(point);
+                    let hierarchy = field.bounds_hierarchy(point).collect::<Vec<_>>();
+                    let (last, outer) = hierarchy.split_last().unwrap();
+                    assert!(last.terminated() && outer.iter().all(|b| !b.terminated()));
+
+                    for (level, bounds) in hierarchy.iter().enumerate() {
+                        assert_eq!(bounds.level(), level);
+                        assert!(bounds.contains(value));
+                        let aabb = bounds.aabb();
+                        assert_eq!(aabb.heights, bounds.heights());
+                        for ((n, lower), upper) in point.into_iter().zip(aabb.lower).zip(aabb.upper)
+                        {
+                            assert!((lower..upper).contains(&(n as f64)));
+                            assert_eq!(upper - lower, bounds.size() as f64);
+                        }
+                    }
+                    let slack = noise * 1e-12;
+                    for pair in hierarchy.windows(2) {
+                        let (outer, inner) = (pair[0].aabb(), pair[1].aabb());
+                        for a in 0..2 {
+                            assert!(outer.lower[a] <= inner.lower[a]);
+                            assert!(inner.upper[a] <= outer.upper[a]);
+                        }
+                        assert!(outer.heights.start() - slack <= *inner.heights.start());
+                        assert!(inner.heights.end() - slack <= *outer.heights.end());
+                    }
+                }
+            }
+        }
+    }
+
+    const FIELDS: [(f64, f64, i64); 4] = [
+        (1.0, 0.5, 0),
+        (100.0, 0.9, 7),
+        (3.0, 0.3, -12),
+        (1000.0, 0.7, 99),
+    ];
+
+    /// The points `field` has cached so far.
+    fn cached<const N: usize>(field: &FractalNoise<N>) -> Vec<[u32; N]> {
+        field.values().keys().copied().collect()
+    }
+
+    /// Checks the bounds of every field of [`FIELDS`] with `kind` and `subdivision` over the points
+    /// of its first `levels` levels.
+    fn assert_stepped_bounds_hold<const N: usize>(
+        kind: BoundKind,
+        subdivision: Subdivision,
+        levels: usize,
+    ) {
+        for (noise, decay, seed) in FIELDS {
+            let mut field = FractalNoise::<N>::new(noise, decay, seed)
+                .with_bound_kind(kind)
+                .with_subdivision(subdivision);
+            for _ in 0..levels {
+                field.step_midpoints().unwrap();
+            }
+            let points = cached(&field);
+            assert_bounds_hold(&mut field, &points, levels);
+        }
+    }
+
+    #[test]
+    fn geometric_bounds_hold() {
+        assert_stepped_bounds_hold::<1>(BoundKind::Geometric, Subdivision::Cube, 12);
+        assert_stepped_bounds_hold::<2>(BoundKind::Geometric, Subdivision::Cube, 6);
+        assert_stepped_bounds_hold::<3>(BoundKind::Geometric, Subdivision::Cube, 4);
+    }
+
+    #[test]
+    fn dimensional_bounds_hold() {
+        assert_stepped_bounds_hold::<1>(BoundKind::Dimensional, Subdivision::Cube, 12);
+        assert_stepped_bounds_hold::<2>(BoundKind::Dimensional, Subdivision::Cube, 6);
+        assert_stepped_bounds_hold::<3>(BoundKind::Dimensional, Subdivision::Cube, 4);
+    }
+
+    #[test]
+    fn simplex_bounds_hold() {
+        assert_stepped_bounds_hold::<2>(BoundKind::Geometric, Subdivision::Simplex, 6);
+        assert_stepped_bounds_hold::<3>(BoundKind::Dimensional, Subdivision::Simplex, 4);
+    }
+
+    #[test]
+    fn simplex_matches_cube() {
+        for (noise, decay, seed) in FIELDS {
+            let mut cube = FractalNoise::<4>::new(noise, decay, seed);
+            let mut simplex = cube.clone().with_subdivision(Subdivision::Simplex);
+            for point in scattered::<4>(seed as u64, 50) {
+                assert_eq!(
+                    cube.find_point(point).to_bits(),
+                    simplex.find_point(point).to_bits()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn simplex_touches_fewer_points() {
+        let mut cube = FractalNoise::<6>::new(1.0, 0.9, 0);
+        let mut simplex = cube.clone().with_subdivision(Subdivision::Simplex);
+        let point = scattered::<6>(0, 1)[0];
+        cube.find_point(point);
+        simplex.find_point(point);
+        assert!(simplex.values().len() <= 1 + 32 * 7);
+        assert!(cube.values().len() > 8 * simplex.values().len());
+    }
+
+    #[test]
+    fn dimensional_bounds_are_tighter() {
+        for (noise, decay, seed) in FIELDS {
+            let geometric = FractalNoise::<1>::new(noise, decay, seed);
+            let dimensional = geometric.clone().with_bound_kind(BoundKind::Dimensional);
+            assert!(dimensional.displacement_bound(0) < geometric.displacement_bound(0) * 0.5);
+            for level in 0..32 {
+                assert!(
+                    dimensional.displacement_bound(level)
+                        <= geometric.displacement_bound(level) * 0.5
+                );
+            }
+
+            let geometric = FractalNoise::<2>::new(noise, decay, seed);
+            let dimensional = geometric.clone().with_bound_kind(BoundKind::Dimensional);
+            for level in 0..32 {
+                let half = geometric.displacement_bound(level) * 0.5;
+                assert!((dimensional.displacement_bound(level) - half).abs() <= half * 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn dimensional_bounds_match_the_extremes() {
+        // with a single level of noise the furthest excursion is one displacement, which some
+        // seed comes close to
+        let mut closest = 0f64;
+        for seed in 0..1000 {
+            let mut field =
+                FractalNoise::<2>::new(1.0, 1e-9, seed).with_bound_kind(BoundKind::Dimensional);
+            field.step_midpoints().unwrap();
+            let bound = field.displacement_bound(0);
+            let root = field.find_point([0, 0]);
+            for &value in field.values().values() {
+                let excursion = (value - root).abs() / bound;
+                assert!(excursion <= 1.0, "{excursion}");
+                closest = closest.max(excursion);
+            }
+        }
+        assert!(closest > 0.99, "{closest}");
+
+        // deeper down no seed gets past the bound
+        for seed in 0..200 {
+            let mut field =
+                FractalNoise::<2>::new(1.0, 0.5, seed).with_bound_kind(BoundKind::Dimensional);
+            for _ in 0..5 {
+                field.step_midpoints().unwrap();
+            }
+            let bound = field.displacement_bound(0);
+            let root = field.find_point([0, 0]);
+            for &value in field.values().values() {
+                assert!((value - root).abs() <= bound, "{value} ({seed})");
+            }
+        }
+    }
+
+    #[test]
+    fn ray_hits_describe_the_cell() {
+        let mut noise = FractalNoise::<2>::new(1.0, 0.5, 3);
+        let ray = Ray::new(
+            Vector3::new(0.3, -1.0, 0.2),
+            Point3::new(1e9 + 0.5, 4.0, 2e9 + 0.5),
+        );
+        let hit = ray.intersect(&mut noise, 0.0..f64::MAX).unwrap();
+        assert!((ray.origin + ray.direction * hit.t - hit.point).magnitude() < 1e-6);
+        assert!(hit.steps > 0 && hit.normal.y > 0.0);
+        assert!((hit.normal.magnitude() - 1.0).abs() < 1e-12);
+        assert_eq!(hit.size, 1 << (32 - hit.level));
+        assert_eq!(hit.exact, hit.level >= 32);
+        for (n, base) in [hit.point.x, hit.point.z].into_iter().zip(hit.base) {
+            assert!(n >= base as f64 && n <= base as f64 + hit.size as f64);
+        }
+
+        assert_eq!(ray.intersect(&mut noise, 0.0..hit.t * 0.5), None);
+        let again = ray.intersect(&mut noise, hit.t * 0.5..f64::MAX).unwrap();
+        assert!((again.point - hit.point).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn rays_hit_the_patch() {
+        // the noise dies out after a few levels, leaving wide terminated cells
+        let mut noise = FractalNoise::<2>::new(1.0, 0.1, 7);
+        for patch in [Patch::Bilinear, Patch::Triangles] {
+            for (i, [x, z]) in scattered::<2>(5, 20).into_iter().enumerate() {
+                let ray = Ray::new(
+                    Vector3::new(1.0 - i as f64 * 0.1, -0.5, 0.3),
+                    Point3::new(x as f64 * 0.5 + 0.5, 3.0, z as f64 * 0.5 + 0.5),
+                )
+                .with_patch(patch);
+                let hit = ray.intersect(&mut noise, 0.0..f64::MAX).unwrap();
+                assert!(!hit.exact && hit.level < 32);
+
+                let size = hit.size as u32;
+                let corners = [[0, 0], [size, 0], [0, size], [size, size]].map(|[dx, dz]| {
+                    noise.find_point([hit.base[0].wrapping_add(dx), hit.base[1].wrapping_add(dz)])
+                });
+                let fractions = [(hit.point.x, hit.base[0]), (hit.point.z, hit.base[1])]
+                    .map(|(n, b)| (n - b as f64) / hit.size as f64);
+                assert!((patch.height(corners, fractions) - hit.point.y).abs() < 1e-6);
+                assert!(
+                    (patch.normal(corners, fractions, hit.size as f64) - hit.normal).magnitude()
+                        < 1e-9
+                );
+            }
+        }
+    }
+
+    /// The height of the bilinear surface over the unit cells of the finest lattice under `at`.
+    fn finest_surface(noise: &mut FractalNoise<2>, at: Point3<f64>) -> f64 {
+        let cell = [at.x, at.z].map(|n| n.floor());
+        let fractions = [at.x - cell[0], at.z - cell[1]];
+        let [x, z] = cell.map(|n| n as u32);
+        let corners = [[0, 0], [1, 0], [0, 1], [1, 1]]
+            .map(|[i, j]| noise.find_point([x.wrapping_add(i), z.wrapping_add(j)]));
+        Patch::Bilinear.height(corners, fractions)
+    }
+
+    /// Marches `ray` up to `end` over the finest lattice, checking that it stays on the side of the
+    /// surface it started on, give or take `tolerance`, until it reaches `hit`, and returning where
+    /// it first got further than that across if it did.
+    fn brute_force_march(
+        noise: &mut FractalNoise<2>,
+        ray: &Ray,
+        end: f64,
+        tolerance: f64,
+        hit: Option<f64>,
+    ) -> Option<f64> {
+        let mut side = |t: f64| {
+            let at = ray.origin + ray.direction * t;
+            at.y - finest_surface(noise, at)
+        };
+        let start = side(0.0).signum();
+        // a twentieth of a lattice step at a time, or a thousandth of the way for steep rays
+        let across = ray.direction.x.hypot(ray.direction.z);
+        let dt = (0.05 / across).min(end / 1000.0);
+        let mut t = 0.0;
+        while t <= end {
+            let crossed = side(t) * start < -tolerance;
+            if hit.is_some_and(|hit| t < hit) {
+                assert!(
+                    !crossed,
+                    "the ray crossed the surface at {t}, before its hit"
+                );
+            } else if crossed {
+                return Some(t);
+            }
+            t += dt;
         }
         None
     }
+
+    #[test]
+    fn rays_match_a_brute_force_march() {
+        // one field rough enough to need every level, one smooth enough to stop at coarse cells
+        for (noise, offset, exact) in [(1e9, 5.0, true), (100.0, 0.5, false)] {
+            let mut noise = FractalNoise::<2>::new(noise, 0.5, 11);
+            let slopes = [-1.0, -0.3, -0.05, -1e-3, -1e-6, 0.0, 1e-3];
+            let mut rays = scattered::<3>(17, 28)
+                .into_iter()
+                .enumerate()
+                .map(|(i, [x, z, a])| {
+                    let angle = a as f64 / u32::MAX as f64 * std::f64::consts::TAU;
+                    let (x, z) = (x as f64 + 0.3, z as f64 + 0.6);
+                    // above the surface, and a few rising from below it
+                    let above = if i % 4 == 3 { -offset } else { offset };
+                    let height = noise.find_point([x as u32, z as u32]) + above;
+                    let slope = slopes[i % slopes.len()] * above.signum();
+                    Ray::new(
+                        Vector3::new(angle.cos(), slope, angle.sin()),
+                        Point3::new(x, height, z),
+                    )
+                })
+                .collect::<Vec<_>>();
+            // along the axes, straight down and straight up
+            let [x, z] = [1 << 31, 12345].map(|n: u32| n as f64 + 0.5);
+            let y = noise.find_point([x as u32, z as u32]);
+            rays.extend([
+                Ray::new(Vector3::new(1.0, -0.01, 0.0), Point3::new(x, y + offset, z)),
+                Ray::new(
+                    Vector3::new(0.0, -0.01, -1.0),
+                    Point3::new(x, y + offset, z),
+                ),
+                Ray::new(Vector3::new(0.0, -1.0, 0.0), Point3::new(x, y + offset, z)),
+                Ray::new(Vector3::new(0.0, 1.0, 0.0), Point3::new(x, y - offset, z)),
+            ]);
+
+            let (end, mut hits) = (2000.0, 0);
+            for ray in rays {
+                let hit = ray.intersect(&mut noise, 0.0..end);
+                // a terminated cell may stray from the finest lattice by what is left below it
+                let tolerance = hit.map_or(1e-6, |hit| match hit.exact {
+                    true => 1e-6,
+                    false => noise.displacement_bound(hit.level) + 1e-6,
+                });
+                if let Some(hit) = hit {
+                    assert_eq!(hit.exact, exact, "{hit:?}");
+                    let surface = finest_surface(&mut noise, hit.point);
+                    assert!(
+                        (hit.point.y - surface).abs() <= tolerance,
+                        "{hit:?} is off the surface at {surface}"
+                    );
+                    hits += 1;
+                }
+                let crossing =
+                    brute_force_march(&mut noise, &ray, end, tolerance, hit.map(|hit| hit.t));
+                assert!(
+                    hit.is_some() || crossing.is_none(),
+                    "the march crossed the surface at {crossing:?}, but the ray missed it"
+                );
+            }
+            assert!(hits > 15, "only {hits} rays hit");
+        }
+    }
+
+    #[test]
+    fn shadow_rays_agree_with_hits() {
+        let mut noise = FractalNoise::<2>::new(1.0, 0.3, 5);
+        let root = noise.find_point([0, 0]);
+        let mut blocked = 0;
+        for (i, [x, z, a]) in scattered::<3>(23, 100).into_iter().enumerate() {
+            let angle = a as f64 / u32::MAX as f64 * std::f64::consts::TAU;
+            let origin = Point3::new(x as f64, root + (i % 5) as f64 * 0.2 - 0.4, z as f64);
+            let slope = [-0.5, -1e-8, 0.0, 1e-8, 0.5][i % 5];
+            let ray = Ray::new(Vector3::new(angle.cos(), slope, angle.sin()), origin);
+
+            let below = origin.y < noise.find_point([x, z]);
+            let range = 0.0..1e9;
+            let hit = ray.intersect(&mut noise, range.clone());
+            let occluded = ray.occluded(&mut noise, range);
+            assert_eq!(occluded, below || hit.is_some(), "ray {i}");
+            blocked += occluded as usize;
+        }
+        assert!(blocked > 10 && blocked < 90, "{blocked}");
+    }
 }
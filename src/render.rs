@@ -0,0 +1,684 @@
+//! Software rendering of heightfields placed in the world, as seen through a [`Camera`].
+
+use crate::camera::{Camera, R2};
+use crate::field::NoiseField;
+use crate::world::WorldField;
+use crate::{Ray, RayHit};
+use cgmath::{InnerSpace, Vector3};
+use std::error::Error;
+use std::f64::consts::{PI, TAU};
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// What a [`Renderer`] casts its rays at: any [`NoiseField`] on its own lattice, or one placed in
+/// the world by a [`WorldField`] so that the camera and lights work in metres.
+pub trait Surface {
+    /// Like [`Ray::intersect`].
+    fn intersect(&mut self, ray: &Ray, range: Range<f64>) -> Option<RayHit>;
+
+    /// Like [`Ray::occluded`].
+    fn occluded(&mut self, ray: &Ray, range: Range<f64>) -> bool;
+}
+
+impl<F: NoiseField<2>> Surface for F {
+    fn intersect(&mut self, ray: &Ray, range: Range<f64>) -> Option<RayHit> {
+        ray.intersect(self, range)
+    }
+
+    fn occluded(&mut self, ray: &Ray, range: Range<f64>) -> bool {
+        ray.occluded(self, range)
+    }
+}
+
+impl<F: NoiseField<2>> Surface for WorldField<F> {
+    fn intersect(&mut self, ray: &Ray, range: Range<f64>) -> Option<RayHit> {
+        WorldField::intersect(self, ray, range)
+    }
+
+    fn occluded(&mut self, ray: &Ray, range: Range<f64>) -> bool {
+        WorldField::occluded(self, ray, range)
+    }
+}
+
+/// Light falling on the terrain, in linear RGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    /// Light reaching every point equally.
+    Ambient { colour: [f32; 3] },
+    /// Parallel light shading surfaces by the cosine of their angle to `direction`, which points
+    /// from the terrain towards the light, such as the sun.
+    Directional {
+        direction: Vector3<f64>,
+        colour: [f32; 3],
+        shadows: Shadows,
+    },
+    /// Light from the whole sky, which lights an open plain like [`Light::Ambient`] but is
+    /// occluded by the terrain around valleys and crevices. It is sampled along `samples`
+    /// directions spread over the hemisphere around the normal, denser towards the normal.
+    Sky { colour: [f32; 3], samples: usize },
+}
+
+/// How a [`Light::Directional`] is blocked by the terrain.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Shadows {
+    #[default]
+    None,
+    /// A single shadow ray towards the light.
+    Hard,
+    /// `samples` shadow rays spread over a light `angle` radians across, such as about `0.0093`
+    /// for the sun, darkening by the fraction blocked.
+    Soft { angle: f64, samples: usize },
+}
+
+/// The colour of the terrain at a height in metres, in linear RGB.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColourMap {
+    /// Black at zero, brightening with the cube of the height until white at four fifths of
+    /// `max`.
+    Gray { max: f64 },
+    /// Blends between `(height, colour)` stops given in increasing height, holding the end colours
+    /// beyond them.
+    Gradient(Vec<(f64, [f32; 3])>),
+}
+
+impl ColourMap {
+    /// Water, lowlands, rock and snow from `low` to `high`.
+    pub fn terrain(low: f64, high: f64) -> Self {
+        let at = |f: f64| low + (high - low) * f;
+        Self::Gradient(vec![
+            (at(0.0), [0.05, 0.15, 0.35]),
+            (at(0.3), [0.2, 0.4, 0.6]),
+            (at(0.32), [0.75, 0.7, 0.5]),
+            (at(0.4), [0.25, 0.5, 0.2]),
+            (at(0.7), [0.45, 0.4, 0.35]),
+            (at(0.9), [0.95, 0.95, 0.95]),
+        ])
+    }
+
+    pub fn colour(&self, height: f64) -> [f32; 3] {
+        match self {
+            Self::Gray { max } => {
+                let gray = ((height / max).powi(3) * 2.0).clamp(0.0, 1.0) as f32;
+                [gray; 3]
+            }
+            Self::Gradient(stops) => {
+                let above = stops.partition_point(|&(h, _)| h <= height);
+                match (above.checked_sub(1).map(|i| stops[i]), stops.get(above)) {
+                    (Some((low, a)), Some(&(high, b))) => {
+                        let f = ((height - low) / (high - low)) as f32;
+                        [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * f)
+                    }
+                    (Some((_, colour)), None) | (None, Some(&(_, colour))) => colour,
+                    (None, None) => [0.0; 3],
+                }
+            }
+        }
+    }
+}
+
+/// `colour` clamped to `0..=1` and scaled to bytes, without gamma correction.
+pub fn to_rgb8(colour: [f32; 3]) -> [u8; 3] {
+    colour.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// A rectangle of pixels rendered in one go, `origin` being its top left pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub origin: [u32; 2],
+    pub size: [u32; 2],
+}
+
+/// How many of the tiles of a render are done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> f64 {
+        self.done as f64 / self.total.max(1) as f64
+    }
+}
+
+/// Linear RGBA pixels, row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    resolution: [u32; 2],
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Image {
+    pub fn new(resolution: [u32; 2], fill: [f32; 4]) -> Self {
+        let len = resolution[0] as usize * resolution[1] as usize;
+        Self {
+            resolution,
+            pixels: vec![fill; len],
+        }
+    }
+
+    pub fn resolution(&self) -> [u32; 2] {
+        self.resolution
+    }
+
+    pub fn pixels(&self) -> &[[f32; 4]] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, [x, y]: [u32; 2]) -> [f32; 4] {
+        self.pixels[y as usize * self.resolution[0] as usize + x as usize]
+    }
+
+    /// Copies the pixels of `tile`, given row by row, into place.
+    pub fn blit(&mut self, tile: Tile, pixels: &[[f32; 4]]) {
+        let width = tile.size[0] as usize;
+        for (row, source) in pixels.chunks_exact(width).enumerate() {
+            let start = (tile.origin[1] as usize + row) * self.resolution[0] as usize
+                + tile.origin[0] as usize;
+            self.pixels[start..start + width].copy_from_slice(source);
+        }
+    }
+
+    /// Three bytes per pixel, dropping alpha.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&[r, g, b, _]| to_rgb8([r, g, b]))
+            .collect()
+    }
+
+    pub fn write_png(&self, writer: impl Write) -> Result<(), Box<dyn Error>> {
+        let [width, height] = self.resolution;
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.to_rgb8())?;
+        Ok(())
+    }
+}
+
+/// Renders a [`Surface`] through a camera, shading every hit by the colour map and lights, or
+/// uniformly lit without any lights, and averaging `samples` rays over each pixel. The image is
+/// split into square tiles shared out between threads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Renderer {
+    camera: Camera,
+    resolution: [u32; 2],
+    colour_map: ColourMap,
+    lights: Vec<Light>,
+    background: [f32; 4],
+    samples: usize,
+    tile_size: u32,
+    threads: usize,
+}
+
+impl Renderer {
+    /// Renders on as many threads as are available, with transparent black where rays miss.
+    pub fn new(camera: Camera, resolution: [u32; 2], colour_map: ColourMap) -> Self {
+        Self {
+            camera,
+            resolution,
+            colour_map,
+            lights: Vec::new(),
+            background: [0.0; 4],
+            samples: 1,
+            tile_size: 32,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    pub fn with_lights(mut self, lights: Vec<Light>) -> Self {
+        self.lights = lights;
+        self
+    }
+
+    pub fn with_background(mut self, background: [f32; 4]) -> Self {
+        self.background = background;
+        self
+    }
+
+    /// Casts `samples` rays through every pixel along [`Camera::sample_rays`] rather than one
+    /// through its centre, smoothing edges at the cost of as many more rays.
+    pub fn with_samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn with_tile_size(mut self, tile_size: u32) -> Self {
+        self.tile_size = tile_size.max(1);
+        self
+    }
+
+    /// Rendering on a single thread never spawns one, as on `wasm32`.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn resolution(&self) -> [u32; 2] {
+        self.resolution
+    }
+
+    pub fn colour_map(&self) -> &ColourMap {
+        &self.colour_map
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn background(&self) -> [f32; 4] {
+        self.background
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// The tiles covering the image, row by row from the top left.
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        let [width, height] = self.resolution;
+        let size = self.tile_size;
+        (0..height).step_by(size as usize).flat_map(move |y| {
+            (0..width).step_by(size as usize).map(move |x| Tile {
+                origin: [x, y],
+                size: [size.min(width - x), size.min(height - y)],
+            })
+        })
+    }
+
+    /// The pixels of `tile`, row by row.
+    pub fn render_tile<S: Surface + ?Sized>(&self, field: &mut S, tile: Tile) -> Vec<[f32; 4]> {
+        let [x0, y0] = tile.origin;
+        (y0..y0 + tile.size[1])
+            .flat_map(|y| (x0..x0 + tile.size[0]).map(move |x| [x, y]))
+            .map(|pixel| {
+                let mut sum = [0f32; 4];
+                let rays = self
+                    .camera
+                    .sample_rays(pixel, self.resolution, self.samples);
+                for ray in rays {
+                    let sample = match field.intersect(&ray, 0.0..f64::MAX) {
+                        Some(hit) => {
+                            let [r, g, b] = self.shade(field, &hit);
+                            [r, g, b, 1.0]
+                        }
+                        None => self.background,
+                    };
+                    for c in 0..4 {
+                        sum[c] += sample[c];
+                    }
+                }
+                sum.map(|c| c / self.samples as f32)
+            })
+            .collect()
+    }
+
+    pub fn render<S: Surface + Clone + Send>(&self, field: &mut S) -> Image {
+        self.render_with_progress(field, |_| {})
+    }
+
+    /// Like [`Renderer::render`] on the calling thread alone, whatever [`Renderer::threads`] says,
+    /// for surfaces that can't be cloned, such as a [`WorldField`] borrowing its field.
+    pub fn render_serial<S: Surface + ?Sized>(&self, field: &mut S) -> Image {
+        let mut image = Image::new(self.resolution, self.background);
+        for tile in self.tiles() {
+            image.blit(tile, &self.render_tile(field, tile));
+        }
+        image
+    }
+
+    /// Like [`Renderer::render`], calling `progress` on the calling thread as tiles finish. The
+    /// calling thread renders into `field` while every other thread renders into its own clone.
+    pub fn render_with_progress<S: Surface + Clone + Send>(
+        &self,
+        field: &mut S,
+        mut progress: impl FnMut(Progress),
+    ) -> Image {
+        let tiles = self.tiles().collect::<Vec<_>>();
+        let next = AtomicUsize::new(0);
+        let take = || tiles.get(next.fetch_add(1, Ordering::Relaxed)).copied();
+
+        let mut image = Image::new(self.resolution, self.background);
+        let mut done = 0;
+        let mut finish = |tile: Tile, pixels: Vec<[f32; 4]>| {
+            image.blit(tile, &pixels);
+            done += 1;
+            progress(Progress {
+                done,
+                total: tiles.len(),
+            });
+        };
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 1..self.threads.min(tiles.len()) {
+                let (sender, mut field, take) = (sender.clone(), field.clone(), &take);
+                scope.spawn(move || {
+                    while let Some(tile) = take() {
+                        if sender
+                            .send((tile, self.render_tile(&mut field, tile)))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            while let Some(tile) = take() {
+                finish(tile, self.render_tile(field, tile));
+                for (tile, pixels) in receiver.try_iter() {
+                    finish(tile, pixels);
+                }
+            }
+            for (tile, pixels) in receiver {
+                finish(tile, pixels);
+            }
+        });
+        image
+    }
+
+    fn shade<S: Surface + ?Sized>(&self, field: &mut S, hit: &RayHit) -> [f32; 3] {
+        let albedo = self.colour_map.colour(hit.point.y);
+        if self.lights.is_empty() {
+            return albedo;
+        }
+        // lift shadow rays off the surface by about the precision the hit was found to
+        let origin = hit.point + hit.normal * (hit.t.max(1.0) * SHADOW_BIAS);
+        let mut visible = |direction: Vector3<f64>| {
+            direction.y > 0.0 && !field.occluded(&Ray::new(direction, origin), 0.0..f64::MAX)
+        };
+        let spin = (hit.point.x * R2[0] + hit.point.z * R2[1]).fract() * TAU;
+
+        let mut light = [0f32; 3];
+        for source in &self.lights {
+            let (colour, strength) = match *source {
+                Light::Ambient { colour } => (colour, 1.0),
+                Light::Directional {
+                    direction,
+                    colour,
+                    shadows,
+                } => {
+                    let direction = direction.normalize();
+                    let cosine = hit.normal.dot(direction);
+                    let lit = match shadows {
+                        // surfaces facing away are dark anyway
+                        _ if cosine <= 0.0 => 0.0,
+                        Shadows::None => 1.0,
+                        Shadows::Hard => f64::from(u8::from(visible(direction))),
+                        Shadows::Soft { angle, samples } => {
+                            let [u, v] = perpendiculars(direction);
+                            let seen = (0..samples)
+                                .filter(|&i| {
+                                    let (r, phi) = sunflower(i, samples, spin);
+                                    let offset = u * phi.cos() + v * phi.sin();
+                                    visible(direction + offset * (r * angle / 2.0).tan())
+                                })
+                                .count();
+                            seen as f64 / samples.max(1) as f64
+                        }
+                    };
+                    (colour, (cosine.max(0.0) * lit) as f32)
+                }
+                Light::Sky { colour, samples } => {
+                    let [u, v] = perpendiculars(hit.normal);
+                    let seen = (0..samples)
+                        .filter(|&i| {
+                            // points spread evenly over the disc lift onto the hemisphere by
+                            // cosine
+                            let (r, phi) = sunflower(i, samples, spin);
+                            let rise = (1.0 - r * r).sqrt();
+                            visible((u * phi.cos() + v * phi.sin()) * r + hit.normal * rise)
+                        })
+                        .count();
+                    (colour, (seen as f64 / samples.max(1) as f64) as f32)
+                }
+            };
+            for c in 0..3 {
+                light[c] += colour[c] * strength;
+            }
+        }
+        [0, 1, 2].map(|c| albedo[c] * light[c])
+    }
+}
+
+/// How far shadow rays start off the surface, relative to the distance from the camera.
+const SHADOW_BIAS: f64 = 1e-9;
+
+/// The polar coordinates of point `i` of `count` spread evenly over the unit disc, turned by
+/// `spin`.
+fn sunflower(i: usize, count: usize, spin: f64) -> (f64, f64) {
+    let golden = PI * (3.0 - 5f64.sqrt());
+    (
+        ((i as f64 + 0.5) / count as f64).sqrt(),
+        i as f64 * golden + spin,
+    )
+}
+
+/// Two unit vectors perpendicular to the unit vector `axis` and each other.
+fn perpendiculars(axis: Vector3<f64>) -> [Vector3<f64>; 2] {
+    let other = if axis.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let u = axis.cross(other).normalize();
+    [u, axis.cross(u)]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::camera::Camera;
+    use crate::field::Raster;
+    use crate::render::{to_rgb8, ColourMap, Image, Light, Progress, Renderer, Shadows};
+    use crate::world::{WorldField, WorldTransform};
+    use crate::FractalNoise;
+    use cgmath::{Deg, Point3, Vector3};
+
+    #[test]
+    fn tiles_and_threads_agree() {
+        let transform = WorldTransform::spanning([8192.0, 8192.0], 200.0);
+        let mut world = WorldField::new(FractalNoise::<2>::new(1.0, 0.5, 1), transform);
+        let camera = Camera::perspective(
+            Point3::new(1000.0, 600.0, 1000.0),
+            Point3::new(3000.0, 0.0, 3000.0),
+            Deg(60.0),
+            1.5,
+        );
+        let renderer = Renderer::new(camera, [24, 16], ColourMap::terrain(0.0, 200.0))
+            .with_lights(vec![Light::Directional {
+                direction: Vector3::new(1.0, 1.0, 0.0),
+                colour: [1.0; 3],
+                shadows: Shadows::Hard,
+            }])
+            .with_tile_size(64)
+            .with_threads(1);
+        assert_eq!(renderer.tiles().count(), 1);
+        let single = renderer.render(&mut world);
+
+        let renderer = renderer.with_tile_size(7).with_threads(3);
+        let covered = renderer
+            .tiles()
+            .map(|tile| tile.size[0] * tile.size[1])
+            .sum::<u32>();
+        assert_eq!(covered, 24 * 16);
+        let mut reports = Vec::new();
+        let tiled = renderer.render_with_progress(&mut world, |progress| reports.push(progress));
+        assert_eq!(tiled, single);
+        assert_eq!(reports.len(), 12);
+        assert_eq!(
+            reports.last(),
+            Some(&Progress {
+                done: 12,
+                total: 12
+            })
+        );
+
+        let hits = single.pixels().iter().filter(|pixel| pixel[3] == 1.0);
+        assert!(hits.count() > 24 * 8);
+        assert_eq!(single.to_rgb8().len(), 24 * 16 * 3);
+
+        // a world borrowing its field renders without cloning it, into the same field
+        let mut borrowed = WorldField::new(world.field_mut(), transform);
+        assert_eq!(renderer.render_serial(&mut borrowed), single);
+    }
+
+    #[test]
+    fn fields_render_without_a_world() {
+        let noise = FractalNoise::<2>::new(1.0, 0.5, 3);
+        let camera = Camera::perspective(
+            Point3::new(1000.0, 2.0, 1000.0),
+            Point3::new(1010.0, 0.0, 1010.0),
+            Deg(60.0),
+            1.0,
+        );
+        let renderer = Renderer::new(camera, [8, 8], ColourMap::Gray { max: 1.0 });
+        let bare = renderer.render(&mut noise.clone());
+        let world = renderer.render(&mut WorldField::new(noise, WorldTransform::default()));
+        assert!(bare.pixels().iter().filter(|pixel| pixel[3] == 1.0).count() > 16);
+        for (a, b) in bare.pixels().iter().zip(world.pixels()) {
+            assert!((0..4).all(|c| (a[c] - b[c]).abs() < 1e-6), "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn samples_blend_the_edge_of_the_world() {
+        let flat = Raster::<2>::new(vec![100.0], 0).unwrap();
+        let mut world = WorldField::new(flat, WorldTransform::spanning([1000.0, 1000.0], 1.0));
+        // one pixel straddling the far edge along `x`, half over the plain and half beyond it
+        let camera = Camera::orthographic(
+            Point3::new(1000.0, 300.0, 500.0),
+            Point3::new(1000.0, 0.0, 500.0),
+            1.0,
+            1.0,
+        );
+        let map = ColourMap::Gradient(vec![(0.0, [0.0, 0.0, 0.0]), (200.0, [1.0, 0.5, 0.0])]);
+        let renderer = Renderer::new(camera, [1, 1], map).with_samples(16);
+        assert_eq!(renderer.samples(), 16);
+        let [r, g, b, alpha] = renderer.render(&mut world).pixel([0, 0]);
+        assert!((alpha - 0.5).abs() <= 0.1, "{alpha}");
+        assert!((r - 0.5 * alpha).abs() < 1e-6 && (g - 0.25 * alpha).abs() < 1e-6 && b == 0.0);
+    }
+
+    #[test]
+    fn lights_shade_by_normal() {
+        let flat = Raster::<2>::new(vec![100.0], 0).unwrap();
+        let mut world = WorldField::new(flat, WorldTransform::spanning([1000.0, 1000.0], 1.0));
+        let camera = Camera::orthographic(
+            Point3::new(500.0, 300.0, 500.0),
+            Point3::new(500.0, 0.0, 500.0),
+            10.0,
+            1.0,
+        );
+        let map = ColourMap::Gradient(vec![(0.0, [0.0, 0.0, 0.0]), (200.0, [1.0, 0.5, 0.0])]);
+        assert_eq!(map.colour(100.0), [0.5, 0.25, 0.0]);
+        assert_eq!(map.colour(-5.0), [0.0; 3]);
+        assert_eq!(map.colour(500.0), [1.0, 0.5, 0.0]);
+        assert_eq!(to_rgb8(ColourMap::Gray { max: 1.0 }.colour(0.5)), [64; 3]);
+
+        let mut pixel = |lights| {
+            Renderer::new(camera, [2, 2], map.clone())
+                .with_lights(lights)
+                .render(&mut world)
+                .pixel([1, 1])
+        };
+        assert_eq!(pixel(vec![]), [0.5, 0.25, 0.0, 1.0]);
+        let sun = Light::Directional {
+            direction: Vector3::new(3f64.sqrt(), 1.0, 0.0),
+            colour: [1.0, 1.0, 0.0],
+            shadows: Shadows::Hard,
+        };
+        let lit = pixel(vec![
+            sun,
+            Light::Ambient {
+                colour: [0.0, 0.2, 0.0],
+            },
+        ]);
+        assert!((lit[0] - 0.25).abs() < 1e-6);
+        assert!((lit[1] - 0.175).abs() < 1e-6);
+
+        // nothing stands above a plain to cast shadows or hide the sky
+        let sky = Light::Sky {
+            colour: [0.0, 0.2, 0.0],
+            samples: 16,
+        };
+        assert_eq!(pixel(vec![sun, sky]), lit);
+    }
+
+    #[test]
+    fn terrain_casts_shadows() {
+        let transform = WorldTransform::spanning([8192.0, 8192.0], 400.0);
+        let mut world = WorldField::new(FractalNoise::<2>::new(1.0, 0.5, 2), transform);
+        let camera = Camera::perspective(
+            Point3::new(1000.0, 900.0, 1000.0),
+            Point3::new(3000.0, 0.0, 3000.0),
+            Deg(60.0),
+            1.0,
+        );
+        let mut render = |light| {
+            Renderer::new(camera, [12, 12], ColourMap::Gray { max: 1.0 })
+                .with_lights(vec![light])
+                .render(&mut world)
+        };
+        let sun = |shadows| Light::Directional {
+            direction: Vector3::new(-1.0, 0.15, -0.3),
+            colour: [1.0; 3],
+            shadows,
+        };
+        let open = render(sun(Shadows::None));
+        let hard = render(sun(Shadows::Hard));
+        let soft = render(sun(Shadows::Soft {
+            angle: 0.2,
+            samples: 8,
+        }));
+        let ambient = render(Light::Ambient { colour: [1.0; 3] });
+        let sky = render(Light::Sky {
+            colour: [1.0; 3],
+            samples: 8,
+        });
+
+        let darker = |image: &Image, than: &Image| {
+            let pairs = image.pixels().iter().zip(than.pixels());
+            assert!(pairs.clone().all(|(a, b)| a[0] <= b[0]));
+            pairs.filter(|(a, b)| a[0] < b[0]).count()
+        };
+        assert!(darker(&hard, &open) > 0);
+        // most of the terrain faces the sun unshadowed, so a shadow ray that wrongly hit the
+        // surface it starts from would darken far more of it
+        let hits = open.pixels().iter().filter(|pixel| pixel[3] == 1.0).count();
+        let unshadowed = hard
+            .pixels()
+            .iter()
+            .zip(open.pixels())
+            .filter(|(a, b)| b[3] == 1.0 && a == b)
+            .count();
+        assert!(hits > 12 * 12 / 2, "{hits}");
+        assert!(
+            unshadowed * 4 >= hits * 3,
+            "only {unshadowed} of {hits} are lit"
+        );
+        assert!(darker(&soft, &open) > 0);
+        assert!(darker(&sky, &ambient) > 0);
+        // the penumbra is lit where the edge of the light peeks past the terrain
+        let mut penumbra = soft.pixels().iter().zip(hard.pixels());
+        assert!(penumbra.any(|(a, b)| a[0] > b[0]));
+    }
+}
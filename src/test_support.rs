@@ -0,0 +1,79 @@
+//! Helpers shared by the tests of every module.
+
+use crate::field::NoiseField;
+use crate::Ray;
+use cgmath::{Point3, Vector3};
+
+/// Deterministic points spread over the whole lattice.
+pub fn scattered<const N: usize>(seed: u64, count: usize) -> Vec<[u32; N]> {
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        (z ^ (z >> 31)) as u32
+    };
+    (0..count).map(|_| [(); N].map(|_| next())).collect()
+}
+
+/// Checks that every value of `field` at `points` lies within its height bounds, and within the
+/// bounds of each cell containing it down to `levels`.
+pub fn assert_bounds_hold<const N: usize>(
+    field: &mut impl NoiseField<N>,
+    points: &[[u32; N]],
+    levels: usize,
+) {
+    let range = field.height_bounds();
+    for &point in points {
+        let value = field.find_point(point);
+        assert!(
+            range.contains(&value),
+            "{point:?} = {value} escapes {range:?}"
+        );
+        for level in 0..levels {
+            let bounds = field.cell_bounds(point, level);
+            assert!(
+                bounds.contains(value),
+                "{point:?} = {value} escapes {bounds:?}"
+            );
+        }
+    }
+}
+
+/// Casts steep rays down onto `field`, checking that each lands on the patch of its cell with
+/// the ray above the field everywhere before it.
+pub fn assert_rays_hit(mut field: impl NoiseField<2>) {
+    let top = field.height_bounds().end() + 1.0;
+    for (i, [x, z]) in scattered::<2>(2, 8).into_iter().enumerate() {
+        let ray = Ray::new(
+            Vector3::new(0.3 - i as f64 * 0.1, -1.0, 0.2),
+            Point3::new(x as f64 * 0.5 + 0.5, top, z as f64 * 0.5 + 0.5),
+        );
+        let hit = ray
+            .intersect(&mut field, 0.0..f64::MAX)
+            .unwrap_or_else(|| panic!("ray {i} missed"));
+        assert!(
+            hit.level < 32,
+            "ray {i} only stopped at level {}",
+            hit.level
+        );
+
+        let size = hit.size as u32;
+        let corners = [[0, 0], [size, 0], [0, size], [size, size]].map(|[dx, dz]| {
+            field.find_point([hit.base[0].wrapping_add(dx), hit.base[1].wrapping_add(dz)])
+        });
+        let fractions = [(hit.point.x, hit.base[0]), (hit.point.z, hit.base[1])]
+            .map(|(n, b)| (n - b as f64) / hit.size as f64);
+        assert!((ray.patch().height(corners, fractions) - hit.point.y).abs() < 1e-6);
+
+        for k in 0..100 {
+            let at = ray.origin() + ray.direction() * (hit.t * k as f64 / 100.0);
+            let below = field.find_point([at.x as u32, at.z as u32]);
+            assert!(
+                at.y >= below - 1e-3,
+                "ray {i} passed below the field at {at:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,431 @@
+//! A world of root tiles across the whole signed 64-bit range, each refined like a
+//! [`FractalNoise`](crate::FractalNoise) lattice.
+
+use crate::field::NoiseField;
+use crate::{
+    cell_size, compute_midpoint, displacement_bounds, sample_displacement, slab, BoundKind,
+    CellAabb, CellBounds, HashMap, Ray, RayHit, EPSILON,
+};
+use cgmath::{AbsDiffEq, Vector3};
+use highway::HighwayHasher;
+use std::hash::BuildHasherDefault;
+use std::iter;
+use std::ops::{Range, RangeInclusive};
+
+const TILE: i64 = 1 << 32;
+
+/// Midpoint displacement over signed 64-bit world coordinates. The world is split into tiles the
+/// size of a [`FractalNoise`](crate::FractalNoise) root cell, whose corners are drawn from the
+/// seed and the tile coordinates, and every point is derived from world coordinates alone, so
+/// neighbouring tiles agree on their shared edges. Lattice coordinates wrap around at the ends of
+/// the `i64` range, so the tiles past `i64::MAX` are the ones from `i64::MIN` on, but rays are
+/// traced in `f64` and only reach single lattice points within `2^53` of the origin.
+#[derive(Debug, Clone)]
+pub struct TiledNoise<const N: usize> {
+    values: HashMap<[i64; N], f64>,
+    noise: [f64; 32],
+    bound_kind: BoundKind,
+    displacement_bounds: [f64; 32],
+    base: f64,
+    seed: i64,
+}
+
+impl<const N: usize> TiledNoise<N> {
+    /// Tile corners lie within `noise / 2` of the root height of `FractalNoise::new(noise, decay,
+    /// seed)`, and every level below them displaces like it.
+    pub fn new(noise: f64, decay: f64, seed: i64) -> Self {
+        let noise: [f64; 32] = iter::successors(Some(noise), |&prev| Some(prev * decay))
+            .take(32)
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        let mut result = Self {
+            values: HashMap::with_hasher(BuildHasherDefault::<HighwayHasher>::default()),
+            noise,
+            bound_kind: BoundKind::default(),
+            displacement_bounds: [0.0; 32],
+            base: noise.iter().sum::<f64>() / 2.0,
+            seed,
+        };
+        result.update_bounds();
+        result
+    }
+
+    pub fn with_bound_kind(mut self, kind: BoundKind) -> Self {
+        self.bound_kind = kind;
+        self.update_bounds();
+        self
+    }
+
+    fn update_bounds(&mut self) {
+        let displacements = self.noise.map(|n| n * 0.5);
+        self.displacement_bounds = displacement_bounds::<N>(self.bound_kind, &displacements);
+    }
+
+    pub fn bound_kind(&self) -> BoundKind {
+        self.bound_kind
+    }
+
+    pub fn noise(&self, iterations: usize) -> f64 {
+        self.noise[iterations]
+    }
+
+    pub fn seed(&self) -> i64 {
+        self.seed
+    }
+
+    pub fn values(&self) -> &HashMap<[i64; N], f64> {
+        &self.values
+    }
+
+    /// Every value in the world lies within this range.
+    pub fn height_bounds(&self) -> RangeInclusive<f64> {
+        let spread = self.noise[0] * 0.5 + self.displacement_bounds[0];
+        (self.base - spread)..=(self.base + spread)
+    }
+
+    /// The value at `point`, computing it and its ancestors within its tile if needed.
+    pub fn find_point(&mut self, point: [i64; N]) -> f64 {
+        if let Some(&v) = self.values.get(&point) {
+            return v;
+        }
+
+        let mut base = point.map(|n| n & !(TILE - 1));
+        let mut value = self.corner(base);
+        for level in 0..32 {
+            if base == point {
+                break;
+            }
+            let midpoint = 1i64 << (31 - level);
+            let next = point.map(|n| n & !(midpoint - 1));
+            for combo in 0..1u32 << N {
+                let other = offset(next, midpoint, combo);
+                let v = self.lookup_or_compute(other);
+                if combo == 0 {
+                    value = v;
+                }
+            }
+            base = next;
+        }
+        value
+    }
+
+    /// The bounds of the cell at `level` containing `point`, where level 0 is a whole tile.
+    pub fn cell_bounds(&mut self, point: [i64; N], level: usize) -> TiledCellBounds<N> {
+        let size = 1i64 << (32 - level);
+        let base = point.map(|n| n & !(size - 1));
+        let (min, max) = (0..1u32 << N)
+            .map(|combo| self.find_point(offset(base, size, combo)))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(v), hi.max(v))
+            });
+        let (terminated, bound) = match level {
+            0..32 => (
+                self.noise[level].abs_diff_eq(&0.0, EPSILON),
+                self.displacement_bounds[level],
+            ),
+            _ => (true, 0.0),
+        };
+        TiledCellBounds {
+            terminated,
+            heights: (min - bound)..=(max + bound),
+            base,
+            level,
+        }
+    }
+
+    /// A view of the tile at `tile` as a wrapping [`NoiseField`], for the tools that work on one.
+    pub fn window(&mut self, tile: [i64; N]) -> TileWindow<'_, N> {
+        TileWindow {
+            noise: self,
+            origin: tile.map(|t| t.wrapping_mul(TILE)),
+        }
+    }
+
+    /// The value of the tile corner at `point`, shared by every tile touching it, drawn like a
+    /// displacement of the root height along the edge from the corner to itself.
+    fn corner(&mut self, point: [i64; N]) -> f64 {
+        if let Some(&v) = self.values.get(&point) {
+            return v;
+        }
+        let value = self.base + sample_displacement(point, point, self.noise[0], self.seed);
+        self.values.insert(point, value);
+        value
+    }
+
+    fn lookup_or_compute(&mut self, target: [i64; N]) -> f64 {
+        if let Some(&existing) = self.values.get(&target) {
+            return existing;
+        }
+        let zeros = target
+            .iter()
+            .map(|n| n.trailing_zeros())
+            .min()
+            .unwrap_or(64);
+        if zeros >= 32 {
+            return self.corner(target);
+        }
+        let level = 31 - zeros as usize;
+        let midpoint = 1i64 << zeros;
+        let f = target.map(|n| n & !(midpoint * 2 - 1));
+        let mut s = f;
+        s.iter_mut()
+            .zip(target)
+            .zip(f)
+            .for_each(|((s, t), f)| *s = s.wrapping_add(t.wrapping_sub(f) * 2));
+        let (f_val, s_val) = (self.find_point(f), self.find_point(s));
+        let computed = compute_midpoint(f, s, f_val, s_val, self.noise[level], self.seed);
+        self.values.insert(target, computed);
+        computed
+    }
+}
+
+impl TiledNoise<2> {
+    /// The first hit of `ray`, in world coordinates with `y` up, on the surface with its parameter
+    /// within `range`, marching [`Ray::intersect`] through the [`TileWindow`] of every tile it
+    /// crosses. The hit is in world coordinates, apart from `base`, which is within its tile.
+    /// Beyond `2^53` from the origin, neighbouring lattice points round to the same `f64`, so
+    /// hits there are only as fine as the spacing of the floats.
+    pub fn intersect(&mut self, ray: &Ray, range: Range<f64>) -> Option<RayHit> {
+        let heights = self.height_bounds();
+        let (start, end) = slab(ray.origin.y, ray.direction.y, &heights)?;
+        let (mut t, end) = (range.start.max(start), range.end.min(end));
+        while t <= end {
+            let at = ray.origin + ray.direction * t;
+            // the tile ahead along the ray when `at` is on an edge
+            let tile = [(at.x, ray.direction.x), (at.z, ray.direction.z)].map(|(n, d)| {
+                let n = n / TILE as f64;
+                (if d < 0.0 { n.ceil() - 1.0 } else { n.floor() }) as i64
+            });
+            let [x, z] = tile.map(|n| n.wrapping_mul(TILE) as f64);
+            let (_, x1) = slab(ray.origin.x, ray.direction.x, &(x..=x + TILE as f64))?;
+            let (_, z1) = slab(ray.origin.z, ray.direction.z, &(z..=z + TILE as f64))?;
+            let exit = x1.min(z1).max(t);
+            // the same ray, measured from the corner of the tile
+            let local = Ray {
+                origin: ray.origin - Vector3::new(x, 0.0, z),
+                ..*ray
+            };
+            if let Some(hit) = local.intersect(&mut self.window(tile), t..exit.min(end)) {
+                return Some(RayHit {
+                    point: hit.point + Vector3::new(x, 0.0, z),
+                    ..hit
+                });
+            }
+            t = if exit > t { exit } else { t.next_up() };
+        }
+        None
+    }
+}
+
+/// `point` moved by `distance` along every axis set in `combo`.
+fn offset<const N: usize>(mut point: [i64; N], distance: i64, combo: u32) -> [i64; N] {
+    point
+        .iter_mut()
+        .zip(0..N)
+        .for_each(|(n, o)| *n = n.wrapping_add(distance * (combo >> o & 1) as i64));
+    point
+}
+
+/// Like [`CellBounds`], for a cell of a [`TiledNoise`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TiledCellBounds<const N: usize> {
+    terminated: bool,
+    heights: RangeInclusive<f64>,
+    base: [i64; N],
+    level: usize,
+}
+
+impl<const N: usize> TiledCellBounds<N> {
+    pub fn terminated(&self) -> bool {
+        self.terminated
+    }
+
+    pub fn heights(&self) -> RangeInclusive<f64> {
+        self.heights.clone()
+    }
+
+    pub fn base(&self) -> [i64; N] {
+        self.base
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn contains(&self, height: f64) -> bool {
+        self.heights.contains(&height)
+    }
+
+    pub fn aabb(&self) -> CellAabb<N> {
+        let size = (1i64 << (32 - self.level)) as f64;
+        CellAabb {
+            lower: self.base.map(|n| n as f64),
+            upper: self.base.map(|n| n as f64 + size),
+            heights: self.heights(),
+        }
+    }
+}
+
+/// One tile of a [`TiledNoise`] as a [`NoiseField`], with its lattice running from the corner of
+/// the tile. Unlike a field that wraps around, the cells along its far edges reach into the
+/// neighbouring tiles, through [`NoiseField::cell_corner`] and the bounds of those cells, so rays
+/// and searches see the world rather than a repeating tile.
+#[derive(Debug)]
+pub struct TileWindow<'a, const N: usize> {
+    noise: &'a mut TiledNoise<N>,
+    origin: [i64; N],
+}
+
+impl<const N: usize> TileWindow<'_, N> {
+    fn world(&self, point: [u32; N]) -> [i64; N] {
+        let mut world = self.origin;
+        world
+            .iter_mut()
+            .zip(point)
+            .for_each(|(w, p)| *w = w.wrapping_add(p as i64));
+        world
+    }
+}
+
+impl<const N: usize> NoiseField<N> for TileWindow<'_, N> {
+    fn find_point(&mut self, point: [u32; N]) -> f64 {
+        let world = self.world(point);
+        self.noise.find_point(world)
+    }
+
+    fn cell_bounds(&mut self, point: [u32; N], level: usize) -> CellBounds<N> {
+        let size = cell_size(level);
+        let base = point.map(|n| n.checked_div(size).map_or(0, |n| n * size));
+        let bounds = self.noise.cell_bounds(self.world(base), level);
+        CellBounds::new(point, level, bounds.heights(), bounds.terminated())
+    }
+
+    fn cell_corner(&mut self, base: [u32; N], level: usize, combo: u32) -> f64 {
+        let size = 1i64 << (32 - level.min(32));
+        let corner = offset(self.world(base), size, combo);
+        self.noise.find_point(corner)
+    }
+
+    fn height_bounds(&self) -> RangeInclusive<f64> {
+        self.noise.height_bounds()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::field::NoiseField;
+    use crate::test_support::scattered;
+    use crate::tiled::{TiledNoise, TILE};
+    use crate::Ray;
+    use cgmath::{InnerSpace, Point3, Vector3};
+
+    #[test]
+    fn bounds_hold_across_tiles() {
+        let mut world = TiledNoise::<2>::new(10.0, 0.6, 4);
+        let range = world.height_bounds();
+        // spread over sixteen tiles either way from the origin
+        let points = scattered::<2>(4, 200)
+            .into_iter()
+            .map(|point| point.map(|n| (n as i32 as i64) << 4));
+        for point in points {
+            let value = world.find_point(point);
+            assert!(range.contains(&value), "{point:?} = {value}");
+            for level in 0..8 {
+                assert!(world.cell_bounds(point, level).contains(value));
+            }
+        }
+
+        // edges only depend on the points along them, whichever tile computes them first
+        let edge = [-TILE, 12345];
+        let mut fresh = TiledNoise::<2>::new(10.0, 0.6, 4);
+        fresh.find_point([-TILE - 1, 12345]);
+        assert_eq!(fresh.find_point(edge), world.find_point(edge));
+        assert_eq!(
+            world.window([-1, 0]).find_point([0, 12345]),
+            world.find_point(edge)
+        );
+    }
+
+    #[test]
+    fn coordinates_wrap_at_the_ends() {
+        let mut world = TiledNoise::<2>::new(10.0, 0.6, 4);
+        let range = world.height_bounds();
+        for point in [
+            [i64::MAX, 0],
+            [i64::MAX, i64::MAX],
+            [i64::MIN, i64::MAX - 5],
+        ] {
+            let value = world.find_point(point);
+            assert!(range.contains(&value), "{point:?} = {value}");
+            for level in 0..=32 {
+                assert!(world.cell_bounds(point, level).contains(value));
+            }
+        }
+        // the last tile shares its far edge with the first
+        let mut fresh = TiledNoise::<2>::new(10.0, 0.6, 4);
+        assert_eq!(
+            world
+                .window([i64::MAX / TILE, 0])
+                .cell_corner([u32::MAX, 7], 32, 1),
+            fresh.find_point([i64::MIN, 7])
+        );
+    }
+
+    #[test]
+    fn windows_reach_into_their_neighbours() {
+        let mut world = TiledNoise::<2>::new(10.0, 0.6, 4);
+        let mut fresh = TiledNoise::<2>::new(10.0, 0.6, 4);
+        let mut window = world.window([3, -2]);
+        let (corner, near) = (
+            window.cell_corner([255 << 24, 12 << 24], 8, 3),
+            window.find_point([0, 0]),
+        );
+        assert_eq!(corner, fresh.find_point([4 * TILE, -2 * TILE + (13 << 24)]));
+        assert_eq!(
+            window.cell_corner([0, 0], 0, 1),
+            fresh.find_point([4 * TILE, -2 * TILE])
+        );
+        assert_ne!(window.cell_corner([0, 0], 0, 1), near);
+    }
+
+    #[test]
+    fn rays_cross_tiles() {
+        let mut world = TiledNoise::<2>::new(1000.0, 0.5, 9);
+        let top = *world.height_bounds().end();
+        let direction = Vector3::new(1.0, -2e-7, 0.7);
+        let ray = Ray::new(direction, Point3::new(-1.5 * TILE as f64, top, 0.5));
+        let hit = world.intersect(&ray, 0.0..f64::MAX).unwrap();
+        assert!(
+            hit.point.x > -TILE as f64,
+            "{hit:?} did not leave the first tile"
+        );
+        assert!(hit.level < 32);
+
+        // the ray stays above the surface up to the hit
+        let steps = 500;
+        for i in 0..steps {
+            let at = ray.origin + (hit.point - ray.origin) * (i as f64 / steps as f64);
+            let height = world.find_point([at.x.floor() as i64, at.z.floor() as i64]);
+            assert!(at.y >= height - 1.0, "{at:?} is below {height}");
+        }
+
+        // and meets it where a ray starting in the hit's tile does
+        let tile = [hit.point.x, hit.point.z].map(|n| (n / TILE as f64).floor());
+        let entry = hit.t - 1e3;
+        let closer = Ray::new(direction, ray.origin + ray.direction * entry);
+        let again = world.intersect(&closer, 0.0..f64::MAX).unwrap();
+        assert!((again.point - hit.point).magnitude() < 1.0, "{again:?}");
+        assert_eq!(
+            [again.point.x, again.point.z].map(|n| (n / TILE as f64).floor()),
+            tile
+        );
+
+        // and where the same ray does when its parameter runs from far below zero
+        let shift = hit.t + 1e10;
+        let ahead = Ray::new(direction, ray.origin + ray.direction * shift);
+        let behind = world.intersect(&ahead, -1e11..0.0).unwrap();
+        assert!((behind.point - hit.point).magnitude() < 1.0, "{behind:?}");
+        assert!((behind.t + 1e10).abs() < 1.0);
+    }
+}
@@ -0,0 +1,258 @@
+//! Metres in and out of heightfields that live on the raw `u32^2` lattice.
+
+use crate::field::NoiseField;
+use crate::{Patch, Ray, RayHit};
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use std::ops::Range;
+
+const LATTICE: f64 = (1u64 << 32) as f64;
+
+/// Where the lattice sits in the world: `scale` metres per lattice step along `x` and `z`, the
+/// world position of the lattice origin at height zero, and `exaggeration` metres per unit of
+/// height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldTransform {
+    scale: [f64; 2],
+    offset: Vector3<f64>,
+    exaggeration: f64,
+}
+
+impl Default for WorldTransform {
+    fn default() -> Self {
+        Self::new([1.0, 1.0], Vector3::new(0.0, 0.0, 0.0), 1.0)
+    }
+}
+
+impl WorldTransform {
+    pub fn new(scale: [f64; 2], offset: Vector3<f64>, exaggeration: f64) -> Self {
+        Self {
+            scale,
+            offset,
+            exaggeration,
+        }
+    }
+
+    /// Stretches the whole lattice over `extent` metres along `x` and `z`.
+    pub fn spanning(extent: [f64; 2], exaggeration: f64) -> Self {
+        Self::new(
+            extent.map(|e| e / LATTICE),
+            Vector3::new(0.0, 0.0, 0.0),
+            exaggeration,
+        )
+    }
+
+    pub fn with_offset(mut self, offset: Vector3<f64>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn scale(&self) -> [f64; 2] {
+        self.scale
+    }
+
+    pub fn offset(&self) -> Vector3<f64> {
+        self.offset
+    }
+
+    pub fn exaggeration(&self) -> f64 {
+        self.exaggeration
+    }
+
+    pub fn to_lattice(&self, point: Point3<f64>) -> Point3<f64> {
+        Point3::from_vec(self.direction_to_lattice((point - self.offset).to_vec()))
+    }
+
+    pub fn to_world(&self, point: Point3<f64>) -> Point3<f64> {
+        Point3::from_vec(self.direction_to_world(point.to_vec())) + self.offset
+    }
+
+    pub fn direction_to_lattice(&self, direction: Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(
+            direction.x / self.scale[0],
+            direction.y / self.exaggeration,
+            direction.z / self.scale[1],
+        )
+    }
+
+    pub fn direction_to_world(&self, direction: Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(
+            direction.x * self.scale[0],
+            direction.y * self.exaggeration,
+            direction.z * self.scale[1],
+        )
+    }
+
+    /// The unit world normal of a surface whose normal on the lattice is `normal`.
+    pub fn normal_to_world(&self, normal: Vector3<f64>) -> Vector3<f64> {
+        // normals take the inverse transpose, which for a scaling is the inverse
+        self.direction_to_lattice(normal).normalize()
+    }
+
+    /// The lattice point at the corner of the lattice step containing the world position `x`,
+    /// `z`, wrapping around like the lattice, and how far into that step the position lies.
+    pub fn lattice_cell(&self, x: f64, z: f64) -> ([u32; 2], [f64; 2]) {
+        let lattice = self.to_lattice(Point3::new(x, 0.0, z));
+        let coords = [lattice.x, lattice.z];
+        (
+            coords.map(|n| n.floor().rem_euclid(LATTICE) as u32),
+            coords.map(|n| n - n.floor()),
+        )
+    }
+}
+
+/// A field placed in the world by a [`WorldTransform`], answering every query in metres.
+#[derive(Debug, Clone)]
+pub struct WorldField<F> {
+    field: F,
+    transform: WorldTransform,
+}
+
+impl<F: NoiseField<2>> WorldField<F> {
+    pub fn new(field: F, transform: WorldTransform) -> Self {
+        Self { field, transform }
+    }
+
+    pub fn transform(&self) -> &WorldTransform {
+        &self.transform
+    }
+
+    pub fn field(&self) -> &F {
+        &self.field
+    }
+
+    pub fn field_mut(&mut self) -> &mut F {
+        &mut self.field
+    }
+
+    pub fn into_inner(self) -> F {
+        self.field
+    }
+
+    /// The height in metres at `x`, `z`, interpolated bilinearly between lattice points.
+    pub fn height_at(&mut self, x: f64, z: f64) -> f64 {
+        let ([x, z], [fx, fz]) = self.transform.lattice_cell(x, z);
+        let height = Patch::Bilinear.height(self.corners([x, z]), [fx, fz]);
+        height * self.transform.exaggeration + self.transform.offset.y
+    }
+
+    /// The unit surface normal at `x`, `z` in world space, pointing up.
+    pub fn normal_at(&mut self, x: f64, z: f64) -> Vector3<f64> {
+        let ([x, z], [fx, fz]) = self.transform.lattice_cell(x, z);
+        let corners = self.corners([x, z]);
+        self.transform
+            .normal_to_world(Patch::Bilinear.normal(corners, [fx, fz], 1.0))
+    }
+
+    /// The heights in metres on a `counts[0]` by `counts[1]` grid starting at `corner` and
+    /// `spacing` metres apart, with rows along `x`.
+    pub fn sample_grid(
+        &mut self,
+        corner: [f64; 2],
+        spacing: [f64; 2],
+        counts: [usize; 2],
+    ) -> Vec<f64> {
+        (0..counts[0])
+            .flat_map(|i| (0..counts[1]).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                self.height_at(
+                    corner[0] + i as f64 * spacing[0],
+                    corner[1] + j as f64 * spacing[1],
+                )
+            })
+            .collect()
+    }
+
+    /// Like [`Ray::intersect`], with `ray` and `range` in metres and the hit in world space.
+    pub fn intersect(&mut self, ray: &Ray, range: Range<f64>) -> Option<RayHit> {
+        let direction = self.transform.direction_to_lattice(ray.direction);
+        let lattice = Ray::new(direction, self.transform.to_lattice(ray.origin));
+        // every metre along the ray covers this many lattice units
+        let stretch = direction.magnitude();
+        let [start, end] =
+            [range.start, range.end].map(|t| (t * stretch).clamp(f64::MIN, f64::MAX));
+        lattice
+            .intersect(&mut self.field, start..end)
+            .map(|hit| RayHit {
+                point: self.transform.to_world(hit.point),
+                t: hit.t / stretch,
+                normal: self.transform.normal_to_world(hit.normal),
+                ..hit
+            })
+    }
+
+    /// Like [`Ray::occluded`], with `ray` and `range` in metres.
+    pub fn occluded(&mut self, ray: &Ray, range: Range<f64>) -> bool {
+        let direction = self.transform.direction_to_lattice(ray.direction);
+        let lattice = Ray::new(direction, self.transform.to_lattice(ray.origin));
+        let stretch = direction.magnitude();
+        let [start, end] =
+            [range.start, range.end].map(|t| (t * stretch).clamp(f64::MIN, f64::MAX));
+        lattice.occluded(&mut self.field, start..end)
+    }
+
+    fn corners(&mut self, [x, z]: [u32; 2]) -> [f64; 4] {
+        let (x1, z1) = (x.wrapping_add(1), z.wrapping_add(1));
+        [[x, z], [x1, z], [x, z1], [x1, z1]].map(|point| self.field.find_point(point))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::field::Raster;
+    use crate::world::{WorldField, WorldTransform};
+    use crate::{FractalNoise, Ray, RayHit};
+    use cgmath::{AbsDiffEq, InnerSpace, Point3, Vector3};
+
+    #[test]
+    fn transforms_round_trip() {
+        let transform = WorldTransform::spanning([10_000.0, 5_000.0], 3.0)
+            .with_offset(Vector3::new(-200.0, 12.0, 40.0));
+        let point = Point3::new(1234.5, -6.0, 789.0);
+        let lattice = transform.to_lattice(point);
+        assert!(transform.to_world(lattice).abs_diff_eq(&point, 1e-9));
+        assert!((lattice.y - -6.0).abs() < 1e-12);
+
+        let mut flat = WorldField::new(Raster::<2>::new(vec![5.0], 0).unwrap(), transform);
+        assert_eq!(flat.height_at(10.0, 20.0), 27.0);
+        let up = flat.normal_at(10.0, 20.0);
+        assert!(up.abs_diff_eq(&Vector3::new(0.0, 1.0, 0.0), 1e-12));
+    }
+
+    #[test]
+    fn rays_hit_in_metres() {
+        let noise = FractalNoise::<2>::new(1.0, 0.5, 3);
+        let transform = WorldTransform::spanning([4096.0, 4096.0], 100.0)
+            .with_offset(Vector3::new(0.0, 50.0, 0.0));
+        let mut world = WorldField::new(noise.clone(), transform);
+
+        let origin = Point3::new(1000.5, 400.0, 2000.5);
+        let direction = Vector3::new(0.2, -1.0, 0.1);
+        let hit = world.intersect(&Ray::new(direction, origin), 0.0..f64::MAX);
+
+        let mut lattice = noise;
+        let expected = Ray::new(
+            transform.direction_to_lattice(direction),
+            transform.to_lattice(origin),
+        )
+        .intersect(&mut lattice, 0.0..f64::MAX)
+        .map(|hit| transform.to_world(hit.point));
+        assert!(hit.is_some());
+        assert_eq!(hit.map(|hit| hit.point), expected);
+
+        let RayHit {
+            point: hit,
+            t,
+            normal,
+            ..
+        } = hit.unwrap();
+        assert!(((hit - origin).magnitude() - t).abs() < 1e-6);
+        assert!((hit - origin)
+            .normalize()
+            .abs_diff_eq(&direction.normalize(), 1e-6));
+        assert!(normal.y > 0.0);
+        assert!((world.height_at(hit.x, hit.z) - hit.y).abs() < 1.0);
+        assert!(world
+            .intersect(&Ray::new(direction, origin), 0.0..1.0)
+            .is_none());
+    }
+}
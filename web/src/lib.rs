@@ -1,5 +1,6 @@
 use cgmath::num_traits::{FloatConst, Signed};
 use cgmath::{AbsDiffEq, Angle, InnerSpace, Transform, Zero};
+use mid_brownie_testing::render::to_rgb8;
 use mid_brownie_testing::FractalNoise;
 use plotters::style::RGBColor;
 use wasm_bindgen::prelude::*;
@@ -21,7 +22,7 @@ pub struct Chart {
 #[wasm_bindgen]
 impl Chart {
     pub fn new(noise: u32, decay: f64, seed: i64) -> Self {
-        let cache = FractalNoise::new(noise, decay, seed);
+        let cache = FractalNoise::new(noise as f64, decay, seed);
         Self { cache }
     }
 
@@ -44,10 +45,22 @@ impl Chart {
         .map_err(|err| err.to_string())?;
         Ok(())
     }
+
+    /// Renders the terrain as seen from a camera orbiting its centre.
+    pub fn render3d(
+        &mut self,
+        canvas: HtmlCanvasElement,
+        pitch: f64,
+        yaw: f64,
+    ) -> Result<(), JsValue> {
+        render3d::draw(canvas, &mut self.cache, pitch, yaw).map_err(|err| err.to_string())?;
+        Ok(())
+    }
 }
 
 mod plot3d {
     use crate::DrawResult;
+    use mid_brownie_testing::render::ColourMap;
     use mid_brownie_testing::FractalNoise;
     use plotters::chart::ChartBuilder;
     use plotters::drawing::IntoDrawingArea;
@@ -83,7 +96,8 @@ mod plot3d {
         println!("showing {iterations} iterations");
         let midpoint = 1u32.reverse_bits() >> iterations;
 
-        let graymap = |y: &f64| super::graymap(y, max).filled();
+        let colour_map = ColourMap::Gray { max };
+        let graymap = |y: &f64| super::rgb(colour_map.colour(*y)).filled();
         let series = SurfaceSeries::xoz(
             iter::successors(Some(0), |s: &u32| s.checked_add(midpoint)),
             iter::successors(Some(0), |s: &u32| s.checked_add(midpoint)),
@@ -130,7 +144,83 @@ mod plot3d {
     }
 }
 
-fn graymap(y: &f64, max: f64) -> RGBColor {
-    let grayness = ((512.0) * ((1.0 + (*y - max) / max).powi(3))) as u8;
-    RGBColor(grayness, grayness, grayness)
+mod render3d {
+    use crate::DrawResult;
+    use cgmath::{Deg, Point3, Vector3};
+    use mid_brownie_testing::camera::Camera;
+    use mid_brownie_testing::render::{ColourMap, Light, Renderer, Shadows};
+    use mid_brownie_testing::world::{WorldField, WorldTransform};
+    use mid_brownie_testing::FractalNoise;
+    use plotters::drawing::IntoDrawingArea;
+    use plotters_canvas::CanvasBackend;
+    use web_sys::HtmlCanvasElement;
+
+    /// The lattice spans this many metres along both axes.
+    const EXTENT: f64 = 40_000.0;
+
+    pub fn draw(
+        canvas: HtmlCanvasElement,
+        cache3d: &mut FractalNoise<2>,
+        pitch: f64,
+        yaw: f64,
+    ) -> DrawResult<()> {
+        let area = CanvasBackend::with_canvas_object(canvas)
+            .unwrap()
+            .into_drawing_area();
+        let (width, height) = area.dim_in_pixel();
+        let max = cache3d.upper_bound(0);
+
+        // the highest peaks reach a twentieth of the extent
+        let exaggeration = EXTENT * 0.05 / max;
+        // borrow the field, so that the points computed along the way are kept for the next render
+        let mut world = WorldField::new(
+            &mut *cache3d,
+            WorldTransform::spanning([EXTENT, EXTENT], exaggeration),
+        );
+        let centre = Point3::new(EXTENT * 0.5, 0.0, EXTENT * 0.5);
+        let orbit = Vector3::new(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        );
+        let camera = Camera::perspective(
+            centre + orbit * EXTENT * 0.4,
+            centre,
+            Deg(60.0),
+            width as f64 / height as f64,
+        );
+
+        // a late afternoon sun, with shadows cheap enough for a single thread
+        let lights = vec![
+            Light::Directional {
+                direction: Vector3::new(-1.0, 0.4, 0.3),
+                colour: [1.0, 0.95, 0.85],
+                shadows: Shadows::Hard,
+            },
+            Light::Ambient {
+                colour: [0.3, 0.35, 0.45],
+            },
+        ];
+        let colour_map = ColourMap::terrain(0.0, max * exaggeration);
+        let image = Renderer::new(camera, [width, height], colour_map)
+            .with_lights(lights)
+            .render_serial(&mut world);
+
+        for y in 0..height {
+            for x in 0..width {
+                let [r, g, b, alpha] = image.pixel([x, y]);
+                if alpha > 0.0 {
+                    area.draw_pixel((x as i32, y as i32), &super::rgb([r, g, b]))?;
+                }
+            }
+        }
+
+        area.present()?;
+        Ok(())
+    }
+}
+
+fn rgb(colour: [f32; 3]) -> RGBColor {
+    let [r, g, b] = to_rgb8(colour);
+    RGBColor(r, g, b)
 }
@@ -35,6 +35,8 @@ function setupUI() {
     renderStatus.innerText = "WebAssembly loaded!";
     yaw.addEventListener("change", updatePlot);
     pitch.addEventListener("change", updatePlot);
+    yaw.addEventListener("change", updateRender);
+    pitch.addEventListener("change", updateRender);
     iterations.addEventListener("change", updatePlot);
     bound.addEventListener("change", updatePlot);
     yaw.addEventListener("input", updatePlot);
@@ -45,9 +47,10 @@ function setupUI() {
     seed.addEventListener("change", setupCanvas);
     noise.addEventListener("change", setupCanvas);
     decay.addEventListener("change", setupCanvas);
-    seed.addEventListener("input", setupCanvas);
-    noise.addEventListener("input", setupCanvas);
-    decay.addEventListener("input", setupCanvas);
+    // the camera view is too slow to follow a slider, so it waits for the "change" at its end
+    seed.addEventListener("input", updateChart);
+    noise.addEventListener("input", updateChart);
+    decay.addEventListener("input", updateChart);
     window.addEventListener("resize", setupCanvas);
 }
 
@@ -61,17 +64,22 @@ function fixCanvasWidths(actual) {
     actual.height = dpr * size / aspectRatio;
 }
 
-/** Setup canvas to properly handle high DPI and redraw current plot. */
+/** Setup canvas to properly handle high DPI and redraw current plot and camera view. */
 function setupCanvas() {
     fixCanvasWidths(canvas);
-    // fixCanvasWidths(render);
+    fixCanvasWidths(render);
 
+    updateChart();
+    updateRender();
+}
+
+/** Rebuild the field from the sliders and redraw the plot, but not the camera view. */
+function updateChart() {
     const seed_value = BigInt(seed.value)
     const noise_value = Number(noise.value)
     const decay_value = Number(decay.value) / decay.max
     chart = Chart.new(noise_value, decay_value, seed_value)
     updatePlot();
-    // updateRender();
 }
 
 function updatePlot3d() {
@@ -86,8 +94,10 @@ function updatePlot3d() {
 
 function updateRender3d() {
     const context = render.getContext('2d');
-    context.clearRect(0, 0, canvas.width, canvas.height);
-    chart.render3d(canvas);
+    context.clearRect(0, 0, render.width, render.height);
+    let pitch_value = Number(pitch.value) / 100.0;
+    let yaw_value = Number(yaw.value) / 100.0;
+    chart.render3d(render, pitch_value, yaw_value);
 }
 
 /** Redraw currently selected plot. */